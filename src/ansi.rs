@@ -0,0 +1,237 @@
+//! Splitting text into what gets displayed and the ECMA-48 escape sequences
+//! and control functions mixed in with it (colors, hyperlinks, titles, ...),
+//! which take up no space but that every strategy would otherwise count.
+//!
+//! Handled are CSI sequences, the control strings (OSC, including OSC 8
+//! hyperlinks, DCS, SOS, PM and APC), the other `ESC`-introduced sequences,
+//! and C1 controls, in both their 7-bit (`ESC [`) and 8-bit (U+009B) forms.
+//! C0 controls other than `ESC` are left in the text.
+use std::borrow::Cow;
+
+const ESC: char = '\x1b';
+const BEL: char = '\x07';
+/// 8-bit String Terminator.
+const ST: char = '\u{9c}';
+
+/// A piece of a string, see [`tokenize`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Token<'a> {
+    /// Something that's displayed.
+    Text(&'a str),
+    /// A whole escape sequence or control function, including the `ESC` or
+    /// C1 control that starts it.
+    Control(&'a str),
+}
+
+/// Split `s` into [`Token`]s. Sequences that are cut off by the end of `s` are
+/// still returned as a (partial) [`Token::Control`].
+pub fn tokenize(s: &str) -> Tokens<'_> {
+    Tokens { rest: s }
+}
+
+/// Just the text from `s`, without escape sequences or controls.
+pub fn strip(s: &str) -> Cow<'_, str> {
+    let mut tokens = tokenize(s);
+    match (tokens.next(), tokens.next()) {
+        (None, _) => Cow::Borrowed(""),
+        (Some(Token::Text(t)), None) => Cow::Borrowed(t),
+        _ => Cow::Owned(
+            tokenize(s)
+                .filter_map(|t| match t {
+                    Token::Text(t) => Some(t),
+                    Token::Control(_) => None,
+                })
+                .collect(),
+        ),
+    }
+}
+
+fn is_c1(c: char) -> bool {
+    ('\u{80}'..='\u{9f}').contains(&c)
+}
+
+/// Iterator returned by [`tokenize`].
+#[derive(Clone, Debug)]
+pub struct Tokens<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    type Item = Token<'a>;
+    fn next(&mut self) -> Option<Token<'a>> {
+        let first = self.rest.chars().next()?;
+        let (token, rest) = if first == ESC || is_c1(first) {
+            let len = control_len(self.rest);
+            (Token::Control(&self.rest[..len]), &self.rest[len..])
+        } else {
+            let len = self.rest.find(|c| c == ESC || is_c1(c));
+            let (text, rest) = self.rest.split_at(len.unwrap_or(self.rest.len()));
+            (Token::Text(text), rest)
+        };
+        self.rest = rest;
+        Some(token)
+    }
+}
+
+/// What kind of sequence an `ESC` or C1 control starts.
+enum Kind {
+    Csi,
+    /// OSC, DCS, SOS, PM or APC, which run until ST. OSC also ends at BEL.
+    String {
+        bel_ends: bool,
+    },
+    /// Any other escape sequence: intermediates, then a final byte.
+    Escape,
+    /// A C1 control that's complete on its own.
+    Single,
+}
+
+/// Length in bytes of the control at the start of `s`, which must start with
+/// `ESC` or a C1 control.
+fn control_len(s: &str) -> usize {
+    let mut chars = s.char_indices().peekable();
+    let (_, first) = chars.next().unwrap();
+    // Map the 7-bit `ESC x` forms to the C1 control they stand for.
+    let c1 = if first == ESC {
+        match chars.peek() {
+            Some(&(_, c @ '@'..='_')) => {
+                chars.next();
+                char::from_u32(c as u32 + 0x40).unwrap()
+            }
+            Some(_) => ESC,
+            None => return s.len(),
+        }
+    } else {
+        first
+    };
+    let kind = match c1 {
+        ESC => Kind::Escape,
+        '\u{9b}' => Kind::Csi,
+        '\u{9d}' => Kind::String { bel_ends: true },
+        '\u{90}' | '\u{98}' | '\u{9e}' | '\u{9f}' => Kind::String { bel_ends: false },
+        _ => Kind::Single,
+    };
+    match kind {
+        Kind::Single => chars.peek().map_or(s.len(), |&(i, _)| i),
+        Kind::Csi => {
+            // Parameters and intermediates, then a final byte. Anything else
+            // is malformed, so end the sequence there.
+            for (i, c) in chars {
+                match c {
+                    '\x20'..='\x3f' => {}
+                    '\x40'..='\x7e' => return i + 1,
+                    _ => return i,
+                }
+            }
+            s.len()
+        }
+        Kind::Escape => {
+            for (i, c) in chars {
+                match c {
+                    '\x20'..='\x2f' => {}
+                    '\x30'..='\x7e' => return i + 1,
+                    _ => return i,
+                }
+            }
+            s.len()
+        }
+        Kind::String { bel_ends } => {
+            while let Some((i, c)) = chars.next() {
+                match c {
+                    BEL if bel_ends => return i + 1,
+                    ST => return i + c.len_utf8(),
+                    ESC if matches!(chars.peek(), Some(&(_, '\\'))) => return i + 2,
+                    _ => {}
+                }
+            }
+            s.len()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Token::{Control, Text};
+
+    fn tokens(s: &str) -> Vec<Token<'_>> {
+        tokenize(s).collect()
+    }
+
+    #[test]
+    fn csi() {
+        assert_eq!(
+            tokens("a\x1b[1;31mb\x1b[0m"),
+            [
+                Text("a"),
+                Control("\x1b[1;31m"),
+                Text("b"),
+                Control("\x1b[0m")
+            ]
+        );
+        assert_eq!(tokens("\x1b[?25h"), [Control("\x1b[?25h")]);
+        // 8-bit.
+        assert_eq!(tokens("\u{9b}31mred"), [Control("\u{9b}31m"), Text("red")]);
+        // Malformed, so it ends before the newline.
+        assert_eq!(tokens("\x1b[1\nx"), [Control("\x1b[1"), Text("\nx")]);
+    }
+
+    #[test]
+    fn osc() {
+        assert_eq!(
+            tokens("\x1b]0;title\x07x"),
+            [Control("\x1b]0;title\x07"), Text("x")]
+        );
+        assert_eq!(
+            tokens("\x1b]8;;https://example.com\x1b\\link\x1b]8;;\x1b\\"),
+            [
+                Control("\x1b]8;;https://example.com\x1b\\"),
+                Text("link"),
+                Control("\x1b]8;;\x1b\\"),
+            ]
+        );
+        assert_eq!(
+            tokens("\u{9d}0;title\u{9c}x"),
+            [Control("\u{9d}0;title\u{9c}"), Text("x")]
+        );
+    }
+
+    #[test]
+    fn dcs() {
+        // Only ST ends a DCS, not BEL.
+        assert_eq!(
+            tokens("\x1bPq\x07#0\x1b\\x"),
+            [Control("\x1bPq\x07#0\x1b\\"), Text("x")]
+        );
+    }
+
+    #[test]
+    fn other_escapes() {
+        assert_eq!(
+            tokens("\x1b7a\x1b8"),
+            [Control("\x1b7"), Text("a"), Control("\x1b8")]
+        );
+        assert_eq!(tokens("\x1b(Bx"), [Control("\x1b(B"), Text("x")]);
+        // NEL, a C1 control on its own.
+        assert_eq!(
+            tokens("a\u{85}b"),
+            [Text("a"), Control("\u{85}"), Text("b")]
+        );
+    }
+
+    #[test]
+    fn cut_off() {
+        assert_eq!(tokens("a\x1b"), [Text("a"), Control("\x1b")]);
+        assert_eq!(tokens("a\x1b[1;3"), [Text("a"), Control("\x1b[1;3")]);
+        assert_eq!(tokens("\x1b]8;;https://"), [Control("\x1b]8;;https://")]);
+        assert_eq!(tokens("\x1bPq#0"), [Control("\x1bPq#0")]);
+        assert_eq!(tokens("\x1b("), [Control("\x1b(")]);
+    }
+
+    #[test]
+    fn strip_keeps_text() {
+        assert_eq!(strip("abc"), Cow::Borrowed("abc"));
+        assert_eq!(strip("\x1b[1mab\x1b[0mc"), "abc");
+        assert_eq!(strip("\x1b[1m"), "");
+    }
+}
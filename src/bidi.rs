@@ -0,0 +1,70 @@
+//! Putting right-to-left text (Arabic, Hebrew, ...) into the order it's
+//! displayed in, with the [UAX #9] bidi algorithm.
+//!
+//! Whether that's the terminal's job or the program's isn't settled: some
+//! terminals run the algorithm on what's printed, and some draw characters
+//! left to right in whatever order they arrive. Text reordered by
+//! [`reorder`] looks right on the second kind, and gets reversed twice on the
+//! first.
+//!
+//! [UAX #9]: https://unicode.org/reports/tr9
+use crate::ansi;
+use std::borrow::Cow;
+use unicode_bidi::ParagraphBidiInfo;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Part of a line that's all in one direction.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Run<'a> {
+    /// In logical order, like it was in the line.
+    pub text: &'a str,
+    pub rtl: bool,
+}
+
+/// The runs in `line` in the order they're displayed, from left to right.
+/// The direction of the line is taken from its first strong character.
+pub fn visual_runs(line: &str) -> Vec<Run<'_>> {
+    if line.is_empty() {
+        return vec![];
+    }
+    let info = ParagraphBidiInfo::new(line, None);
+    let (levels, runs) = info.visual_runs(0..line.len());
+    runs.into_iter()
+        .map(|run| Run {
+            rtl: levels[run.start].is_rtl(),
+            text: &line[run],
+        })
+        .collect()
+}
+
+/// `s` rearranged into display order, for a terminal that doesn't do bidi
+/// itself. Each line is reordered on its own, and right-to-left runs are
+/// reversed a grapheme cluster at a time, so combining marks stay on the
+/// character they go with. Mirrored characters like brackets aren't swapped.
+/// Escape sequences (see [`crate::ansi`]) are removed, since there's no
+/// telling where they'd belong afterwards.
+pub fn reorder(s: &str) -> Cow<'_, str> {
+    let text = ansi::strip(s);
+    if text
+        .split('\n')
+        .all(|l| !ParagraphBidiInfo::new(l, None).has_rtl())
+    {
+        return text;
+    }
+    let lines = text
+        .split('\n')
+        .map(|line| {
+            visual_runs(line)
+                .into_iter()
+                .map(|run| {
+                    if run.rtl {
+                        run.text.graphemes(true).rev().collect()
+                    } else {
+                        run.text.to_string()
+                    }
+                })
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>();
+    Cow::Owned(lines.join("\n"))
+}
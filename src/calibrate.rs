@@ -0,0 +1,232 @@
+//! `calibrate`: rather than trusting any table, print every codepoint (and
+//! some sequences) at the start of a line and ask the terminal where the
+//! cursor ended up. The results get saved as a
+//! `term_width_blog_example::profile::Profile`.
+use super::{
+    report::Env,
+    term::{Clear, Color, Terminal},
+    Result,
+};
+use std::io::Write;
+use std::path::PathBuf;
+use term_width_blog_example::profile::Profile;
+
+#[derive(argh::FromArgs)]
+#[argh(subcommand, name = "calibrate")]
+/// Measure how wide this terminal draws each codepoint, and save it as a
+/// profile.
+pub struct Args {
+    /// file to write the profile to. if it already exists, calibration picks
+    /// up where it left off.
+    #[argh(option, short = 'o', default = "PathBuf::from(\"width-profile.txt\")")]
+    pub out: PathBuf,
+    /// hex codepoint range to measure, like `1f300-1faff`. may repeat. the
+    /// default is all of unicode, minus controls and surrogates.
+    #[argh(option, short = 'r')]
+    range: Vec<CodepointRange>,
+    /// also measure a built-in set of sequences: flags, emoji modifier,
+    /// keycap and ZWJ sequences, and variation selector pairs.
+    #[argh(switch)]
+    sequences: bool,
+    /// additional grapheme sequence to measure, may repeat.
+    #[argh(option, short = 's')]
+    sequence: Vec<String>,
+    /// how many cursor queries to send before waiting for the replies.
+    #[argh(option, default = "256")]
+    batch: usize,
+    /// start over, rather than resuming from an existing profile.
+    #[argh(switch)]
+    fresh: bool,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct CodepointRange(u32, u32);
+
+impl std::str::FromStr for CodepointRange {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, String> {
+        let parse = |s: &str| {
+            let s = s.trim_start_matches("U+").trim_start_matches("u+");
+            u32::from_str_radix(s, 16).map_err(|e| format!("bad codepoint {:?}: {}", s, e))
+        };
+        let (lo, hi) = match s.split_once('-') {
+            Some((lo, hi)) => (parse(lo)?, parse(hi)?),
+            None => (parse(s)?, parse(s)?),
+        };
+        if lo > hi || hi > 0x10FFFF {
+            return Err(format!("bad range {:?}", s));
+        }
+        Ok(CodepointRange(lo, hi))
+    }
+}
+
+const DEFAULT_RANGE: CodepointRange = CodepointRange(0x20, 0x10FFFF);
+
+// These move the cursor around or otherwise mess with the terminal, so their
+// "width" isn't meaningful.
+fn is_control(c: char) -> bool {
+    matches!(c, '\0'..='\x1f' | '\x7f'..='\u{9f}')
+}
+
+enum Item {
+    Char(char),
+    Seq(String),
+}
+
+impl Item {
+    fn text(&self) -> std::borrow::Cow<'_, str> {
+        match self {
+            Item::Char(c) => c.to_string().into(),
+            Item::Seq(s) => s.as_str().into(),
+        }
+    }
+}
+
+// Well short of everything in emoji-zwj-sequences.txt, but enough to see how
+// the terminal feels about them.
+const ZWJ_SEQUENCES: &[&str] = &["👨‍👩‍👦‍👦", "👩‍💻", "🧑‍🤝‍🧑", "🏳️‍🌈", "🏳️‍⚧️", "🏴‍☠️", "👁️‍🗨️", "❤️‍🔥", "🐻‍❄️", "👩🏽‍🚀"];
+
+// Subdivision flags (England, Scotland, Wales).
+const TAG_SEQUENCES: &[&str] = &[
+    "\u{1f3f4}\u{e0067}\u{e0062}\u{e0065}\u{e006e}\u{e0067}\u{e007f}",
+    "\u{1f3f4}\u{e0067}\u{e0062}\u{e0073}\u{e0063}\u{e0074}\u{e007f}",
+    "\u{1f3f4}\u{e0067}\u{e0062}\u{e0077}\u{e006c}\u{e0073}\u{e007f}",
+];
+
+fn builtin_sequences() -> Vec<String> {
+    let mut seqs = vec![];
+    // Every regional indicator pair, not just the ones that are real flags.
+    let ri = '\u{1f1e6}'..='\u{1f1ff}';
+    for a in ri.clone() {
+        for b in ri.clone() {
+            seqs.push([a, b].iter().collect());
+        }
+    }
+    for c in "0123456789#*".chars() {
+        seqs.push(format!("{}\u{fe0f}\u{20e3}", c));
+    }
+    for c in ('\u{a9}'..='\u{1faff}').filter(|&c| unic_emoji_char::is_emoji(c)) {
+        // Ask for whichever presentation isn't the default.
+        if unic_emoji_char::is_emoji_presentation(c) {
+            seqs.push(format!("{}\u{fe0e}", c));
+        } else {
+            seqs.push(format!("{}\u{fe0f}", c));
+        }
+        if unic_emoji_char::is_emoji_modifier_base(c) {
+            for m in '\u{1f3fb}'..='\u{1f3ff}' {
+                seqs.push([c, m].iter().collect());
+            }
+        }
+    }
+    seqs.extend(ZWJ_SEQUENCES.iter().map(|s| s.to_string()));
+    seqs.extend(TAG_SEQUENCES.iter().map(|s| s.to_string()));
+    seqs
+}
+
+fn header(env: &Env) -> String {
+    let mut s = "# width profile, written by `calibrate`\n".to_string();
+    for (k, v) in &[
+        ("TERM", &env.term),
+        ("TERM_PROGRAM", &env.term_program),
+        ("locale", &env.locale),
+    ] {
+        if let Some(v) = v {
+            s.push_str(&format!("# {}={}\n", k, v));
+        }
+    }
+    if let Some(info) = &env.terminal {
+        s.push_str(&format!("# terminal={}\n", info));
+    }
+    s
+}
+
+pub fn run(t: &mut Terminal, args: &Args) -> Result<()> {
+    let resume = !args.fresh && args.out.exists();
+    let mut profile = if resume {
+        Profile::load(&args.out)?
+    } else {
+        Profile::new()
+    };
+    let env = Env::current(t);
+
+    let ranges = if args.range.is_empty() {
+        &[DEFAULT_RANGE][..]
+    } else {
+        &args.range[..]
+    };
+    let mut todo = ranges
+        .iter()
+        .flat_map(|r| r.0..=r.1)
+        .filter_map(char::from_u32)
+        .filter(|&c| !is_control(c) && profile.char_width(c).is_none())
+        .map(Item::Char)
+        .collect::<Vec<_>>();
+    let mut seqs = args.sequence.clone();
+    if args.sequences {
+        seqs.extend(builtin_sequences());
+    }
+    todo.extend(
+        seqs.into_iter()
+            .filter(|s| profile.sequence_width(s).is_none())
+            .map(Item::Seq),
+    );
+
+    // New results get appended after each batch, so that if this gets
+    // interrupted we lose at most one batch. The file gets rewritten
+    // compactly at the end.
+    let mut file = if resume {
+        std::fs::OpenOptions::new().append(true).open(&args.out)?
+    } else {
+        let mut f = std::fs::File::create(&args.out)?;
+        f.write_all(header(&env).as_bytes())?;
+        f
+    };
+
+    t.clear(Clear::FullScreen)?;
+    let cols = t.size().0 as usize;
+    let mut done = 0;
+    for batch in todo.chunks(args.batch.max(1)) {
+        // The terminal's in raw mode, so ^C doesn't send SIGINT.
+        if t.take_input().iter().any(|&b| b == b'q' || b == 0x03) {
+            break;
+        }
+        for item in batch {
+            t.move_to(1, 1)?;
+            t.clear(Clear::FullLine)?;
+            t.write(&item.text())?;
+            t.request_pos()?;
+        }
+        t.flush()?;
+        let mut found = Profile::new();
+        for item in batch {
+            let (x, y) = t.read_pos_reply()?;
+            let width = y.saturating_sub(1) as usize * cols + x.saturating_sub(1) as usize;
+            match item {
+                Item::Char(c) => found.insert_char(*c, width),
+                Item::Seq(s) => found.insert_sequence(s, width),
+            }
+        }
+        file.write_all(found.to_string().as_bytes())?;
+        for (lo, hi, w) in found.ranges() {
+            profile.insert_range(lo, hi, w);
+        }
+        for (s, w) in found.sequences() {
+            profile.insert_sequence(s, w);
+        }
+        done += batch.len();
+        t.move_to(1, 3)?;
+        t.clear(Clear::FullLine)?;
+        t.write_colored(
+            Color::Yellow,
+            &format!("calibrating: {}/{} (q to stop)", done, todo.len()),
+        )?;
+    }
+    drop(file);
+
+    let mut compact = header(&env);
+    compact.push_str(&profile.to_string());
+    std::fs::write(&args.out, compact)?;
+    t.move_to(1, 1)?;
+    t.clear(Clear::ToEndOfScreen)?;
+    Ok(())
+}
@@ -1,31 +1,62 @@
-//! This is where the code the blog is about lives, although I've cleaned it up some.
+//! Drawing boxes around text using the widths from the library. The measuring
+//! itself lives in `strategies.rs` now, this just puts it on the screen.
 use super::{
-    term::{Clear, Color, Terminal},
+    score::Strategy,
+    term::{self, Clear, Color, Terminal},
     Result,
 };
+use std::borrow::Cow;
+use std::sync::OnceLock;
+use term_width_blog_example::{
+    ansi, pad,
+    profile::Profile,
+    strategies, truncate,
+    wcwidths::{AmbiguousWidth, PrivateUseWidths, UnicodeVersion},
+    wrap, WidthStrategy,
+};
 // use std::io::Write;
 use unicode_normalization::UnicodeNormalization;
 use unicode_segmentation::UnicodeSegmentation;
-use unicode_width::UnicodeWidthStr;
 
 const BOX_COL: Color = Color::Red;
 
 // it's easier to determine how wron something is without the box drawing chars,
 // so only use them if --feature="box_drawing" is passed.
-#[cfg(feature = "box_drawing")]
-mod drawing {
-    pub const HLINE: &str = "─";
-    pub const VLINE: &str = "│";
-    pub const CORNERS: &[char] = &['┌', '┐', '└', '┘'];
-}
-#[cfg(not(feature = "box_drawing"))]
-mod drawing {
-    pub const HLINE: &str = "-";
-    pub const VLINE: &str = "|";
-    pub const CORNERS: &[char] = &['+'; 4];
+pub mod drawing {
+    pub mod box_drawing {
+        pub const HLINE: &str = "─";
+        pub const VLINE: &str = "│";
+        pub const CORNERS: &[char] = &['┌', '┐', '└', '┘'];
+        /// Where lines meet edges: top, bottom, left, right.
+        pub const TEES: &[char] = &['┬', '┴', '├', '┤'];
+        pub const CROSS: char = '┼';
+    }
+    pub mod ascii {
+        pub const HLINE: &str = "-";
+        pub const VLINE: &str = "|";
+        pub const CORNERS: &[char] = &['+'; 4];
+        pub const TEES: &[char] = &['+'; 4];
+        pub const CROSS: char = '+';
+    }
+    #[cfg(not(feature = "box_drawing"))]
+    pub use ascii::*;
+    #[cfg(feature = "box_drawing")]
+    pub use box_drawing::*;
 }
 
-fn wrong_draw_common(t: &mut Terminal, x: u16, y: u16, s: &str, w: usize) -> Result<()> {
+fn wrong_draw_common(
+    t: &mut Terminal,
+    x: u16,
+    y: u16,
+    s: &str,
+    strat: &dyn WidthStrategy,
+) -> Result<()> {
+    let lines = lines(s, Some(strat));
+    let w = lines
+        .iter()
+        .map(|l| visible_width(strat, l))
+        .max()
+        .unwrap_or_default();
     t.move_to(x, y)?;
     let line = drawing::HLINE.repeat(w);
     t.write_colored(
@@ -33,115 +64,374 @@ fn wrong_draw_common(t: &mut Terminal, x: u16, y: u16, s: &str, w: usize) -> Res
         &format!("{}{}{}", drawing::CORNERS[0], line, drawing::CORNERS[1]),
     )?;
     // write!(t, "{}{}{}", drawing::CORNERS[0], line, drawing::CORNERS[1])?;
-    t.move_to(x, y + 1)?;
-    t.write_colored(BOX_COL, drawing::VLINE)?;
-    t.write(s)?;
-    t.write_colored(BOX_COL, drawing::VLINE)?;
-    t.move_to(x, y + 2)?;
+    let mut y = y + 1;
+    for text in &lines {
+        t.move_to(x, y)?;
+        t.write_colored(BOX_COL, drawing::VLINE)?;
+        t.write(text)?;
+        // Pad shorter lines out to the widest, so the right edge (should) line up.
+        t.write(&" ".repeat(w - visible_width(strat, text)))?;
+        t.write_colored(BOX_COL, drawing::VLINE)?;
+        y += 1;
+    }
+    t.move_to(x, y)?;
     t.write_colored(
         BOX_COL,
-        &format!("{}{}{}", drawing::CORNERS[0], line, drawing::CORNERS[1]),
+        &format!("{}{}{}", drawing::CORNERS[2], line, drawing::CORNERS[3]),
     )?;
     Ok(())
 }
 
+/// `--align`: every line of every phrase left, center and right aligned in
+/// columns wide enough for all of them, for each strategy. The bars only line
+/// up if the strategy got the width right.
+pub fn align_grid(strategies: &[&Strategy], phrases: &[String]) -> String {
+    use std::fmt::Write;
+    let width = strategies
+        .iter()
+        .flat_map(|(_, strat)| {
+            phrases
+                .iter()
+                .flat_map(move |p| lines(p, Some(*strat)))
+                .map(move |l| visible_width(*strat, &l))
+        })
+        .max()
+        .unwrap_or_default()
+        + 2;
+    let mut out = String::new();
+    for (name, strat) in strategies {
+        let _ = writeln!(out, "{}", name);
+        for line in phrases.iter().flat_map(|p| lines(p, Some(*strat))) {
+            let p = pad::padded(&line, *strat);
+            let v = drawing::VLINE;
+            let _ = writeln!(out, "{v}{p:<width$}{v}{p:^width$}{v}{p:>width$}{v}");
+        }
+    }
+    out
+}
+
+/// How wide `strat` thinks `s` is, ignoring any escape sequences in it, which
+/// still get written as is.
+fn visible_width(strat: &dyn WidthStrategy, s: &str) -> usize {
+    strat.str_width(&ansi::strip(s))
+}
+
+/// The lines `s` gets drawn as: split at each `\n`, and then either
+/// truncated with `--truncate` (which turns each line into three, cut at the
+/// start, middle and end), word wrapped with `--wrap`, or with `--max-width`,
+/// broken wherever `strat` thinks a line got too wide (between graphemes, like
+/// a terminal's own wrapping would).
+pub fn lines<'a>(s: &'a str, strat: Option<&dyn WidthStrategy>) -> Vec<Cow<'a, str>> {
+    let c = configured();
+    let mut lines = vec![];
+    for line in s.split('\n') {
+        let strat = match strat {
+            Some(strat) if c.truncate.is_some() || c.wrap.is_some() || c.max_width.is_some() => {
+                strat
+            }
+            _ => {
+                lines.push(Cow::Borrowed(line));
+                continue;
+            }
+        };
+        if let Some(width) = c.truncate {
+            lines.push(truncate::truncate_start(line, width, strat));
+            lines.push(truncate::truncate_middle(line, width, strat));
+            lines.push(truncate::truncate_end(line, width, strat));
+            continue;
+        }
+        if let Some(opts) = &c.wrap {
+            lines.extend(
+                wrap::wrap_with(line, opts, strat)
+                    .into_iter()
+                    .map(Cow::Owned),
+            );
+            continue;
+        }
+        let max_width = c.max_width.unwrap_or_default();
+        let mut start = 0;
+        for (i, g) in line.grapheme_indices(true) {
+            let end = i + g.len();
+            if i > start && visible_width(strat, &line[start..end]) > max_width {
+                lines.push(Cow::Borrowed(&line[start..i]));
+                start = i;
+            }
+        }
+        lines.push(Cow::Borrowed(&line[start..]));
+    }
+    lines
+}
+
 pub fn byte_len(t: &mut Terminal, x: u16, y: u16, s: &str) -> Result<()> {
-    wrong_draw_common(t, x, y, s, s.len())
+    wrong_draw_common(t, x, y, s, &strategies::ByteLen)
 }
 
 pub fn codepoints(t: &mut Terminal, x: u16, y: u16, s: &str) -> Result<()> {
-    wrong_draw_common(t, x, y, s, s.chars().count())
+    wrong_draw_common(t, x, y, s, &strategies::Codepoints)
 }
 
 pub fn nfc_codepoints(t: &mut Terminal, x: u16, y: u16, s: &str) -> Result<()> {
     let s = s.nfc().collect::<String>();
-    wrong_draw_common(t, x, y, &s, s.nfc().count())
+    wrong_draw_common(t, x, y, &s, &strategies::NfcCodepoints)
 }
 
 pub fn graphemes(t: &mut Terminal, x: u16, y: u16, s: &str) -> Result<()> {
-    wrong_draw_common(t, x, y, s, s.graphemes(true).count())
+    wrong_draw_common(t, x, y, s, &strategies::Graphemes)
 }
 
 pub fn system_wcwidth(t: &mut Terminal, x: u16, y: u16, s: &str) -> Result<()> {
-    let width = s
-        .chars()
-        .map(|c| crate::wcwidths::system_wcwidth(c).unwrap_or_default())
-        .sum();
-    wrong_draw_common(t, x, y, s, width)
+    wrong_draw_common(t, x, y, s, &strategies::SystemWcwidth)
 }
 
 pub fn unicode_width(t: &mut Terminal, x: u16, y: u16, s: &str) -> Result<()> {
-    wrong_draw_common(t, x, y, s, s.width())
+    wrong_draw_common(t, x, y, s, &configured().unicode_width)
 }
 
 pub fn nfc_unicode_width(t: &mut Terminal, x: u16, y: u16, s: &str) -> Result<()> {
     let s = s.nfc().collect::<String>();
-    wrong_draw_common(t, x, y, &s, s.width())
+    wrong_draw_common(t, x, y, &s, &configured().nfc_unicode_width)
 }
 
 pub fn widecharwidth_fish(t: &mut Terminal, x: u16, y: u16, s: &str) -> Result<()> {
-    let width = s.chars().map(crate::wcwidths::widecharwidth_fish).sum();
-    wrong_draw_common(t, x, y, &s, width)
+    wrong_draw_common(t, x, y, s, &configured().widecharwidth_fish)
 }
 
 pub fn widecharwidth_recommended(t: &mut Terminal, x: u16, y: u16, s: &str) -> Result<()> {
-    let width = s
-        .chars()
-        .map(crate::wcwidths::widecharwidth_recommended)
-        .sum();
-    wrong_draw_common(t, x, y, &s, width)
+    wrong_draw_common(t, x, y, s, &configured().widecharwidth_rec)
 }
 
 pub fn termwiz_ish(t: &mut Terminal, x: u16, y: u16, s: &str) -> Result<()> {
     let s = s.nfc().collect::<String>();
-    let width = s
-        .graphemes(true)
-        .map(|g| {
-            let is_emoji_sequence = g.chars().any(|c| {
-                // This is incomplete, but you could imagine a version which
-                // follows https://unicode.org/reports/tr51.
-                unic_emoji_char::is_emoji_modifier(c)
-                    || unic_emoji_char::is_emoji_modifier_base(c)
-                    // regional indicator sequence.
-                    || (0x1F1E6..=0x1F1FF).contains(&(c as u32))
-            });
-            if is_emoji_sequence {
-                2
-            } else {
-                g.width()
-            }
+    wrong_draw_common(t, x, y, &s, &configured().termwiz_ish)
+}
+
+pub fn uts51(t: &mut Terminal, x: u16, y: u16, s: &str) -> Result<()> {
+    wrong_draw_common(t, x, y, s, &configured().uts51)
+}
+
+pub fn variation_selectors(t: &mut Terminal, x: u16, y: u16, s: &str) -> Result<()> {
+    wrong_draw_common(t, x, y, s, &configured().variation_selectors)
+}
+
+pub fn hangul(t: &mut Terminal, x: u16, y: u16, s: &str) -> Result<()> {
+    wrong_draw_common(t, x, y, s, &configured().hangul)
+}
+
+/// Draws the phrase already reordered for display, so it only looks right
+/// on terminals that don't do bidi themselves.
+pub fn bidi(t: &mut Terminal, x: u16, y: u16, s: &str) -> Result<()> {
+    wrong_draw_common(
+        t,
+        x,
+        y,
+        &term_width_blog_example::bidi::reorder(s),
+        &configured().bidi,
+    )
+}
+
+/// Draws with the grapheme clustering mode turned on, if the terminal has it.
+pub fn mode2027(t: &mut Terminal, x: u16, y: u16, s: &str) -> Result<()> {
+    let prev = t.set_mode(term::GRAPHEME_CLUSTERING, true)?;
+    wrong_draw_common(t, x, y, s, &configured().mode2027)?;
+    t.set_mode(term::GRAPHEME_CLUSTERING, prev.is_set())?;
+    Ok(())
+}
+
+/// Command line settings that change how some of the strategies measure, or
+/// how their boxes are drawn.
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+    pub unicode_version: UnicodeVersion,
+    pub ambiguous: AmbiguousWidth,
+    pub private_use: Option<PrivateUseWidths>,
+    pub ignore_variation_selectors: bool,
+    /// Break lines wider than this (by the strategy drawing them).
+    pub max_width: Option<usize>,
+    /// Word wrap lines instead, which takes precedence over `max_width`.
+    pub wrap: Option<wrap::Options>,
+    /// Show each line truncated to this width in three ways instead, which
+    /// takes precedence over both of the above.
+    pub truncate: Option<usize>,
+}
+
+/// The strategies that depend on [`Options`].
+struct Configured {
+    max_width: Option<usize>,
+    wrap: Option<wrap::Options>,
+    truncate: Option<usize>,
+    unicode_width: strategies::UnicodeWidth,
+    nfc_unicode_width: strategies::NfcUnicodeWidth,
+    widecharwidth_rec: strategies::WidecharwidthRecommended,
+    widecharwidth_fish: strategies::WidecharwidthFish,
+    termwiz_ish: strategies::TermwizIsh,
+    uts51: strategies::Uts51,
+    variation_selectors: strategies::VariationSelectors,
+    hangul: strategies::HangulJamo,
+    bidi: strategies::Bidi,
+    mode2027: strategies::Mode2027,
+}
+
+impl Configured {
+    fn new(opts: &Options) -> Self {
+        Self {
+            max_width: opts.max_width,
+            wrap: opts.wrap,
+            truncate: opts.truncate,
+            unicode_width: strategies::UnicodeWidth {
+                ambiguous: opts.ambiguous,
+            },
+            nfc_unicode_width: strategies::NfcUnicodeWidth {
+                ambiguous: opts.ambiguous,
+            },
+            widecharwidth_rec: strategies::WidecharwidthRecommended {
+                version: opts.unicode_version,
+                ambiguous: opts.ambiguous,
+                private_use: PRIVATE_USE.get(),
+            },
+            widecharwidth_fish: strategies::WidecharwidthFish {
+                version: opts.unicode_version,
+                ambiguous: opts.ambiguous,
+                private_use: PRIVATE_USE.get(),
+            },
+            termwiz_ish: strategies::TermwizIsh {
+                ambiguous: opts.ambiguous,
+            },
+            uts51: strategies::Uts51 {
+                ambiguous: opts.ambiguous,
+            },
+            variation_selectors: strategies::VariationSelectors {
+                ignore: opts.ignore_variation_selectors,
+                ambiguous: opts.ambiguous,
+            },
+            hangul: strategies::HangulJamo {
+                ambiguous: opts.ambiguous,
+            },
+            bidi: strategies::Bidi {
+                ambiguous: opts.ambiguous,
+            },
+            mode2027: strategies::Mode2027 {
+                ambiguous: opts.ambiguous,
+            },
+        }
+    }
+}
+
+static CONFIGURED: OnceLock<Configured> = OnceLock::new();
+/// Separate from [`CONFIGURED`] since the strategies in it borrow this.
+static PRIVATE_USE: OnceLock<PrivateUseWidths> = OnceLock::new();
+
+/// Set up the strategies from the command line. Needs to happen before
+/// anything gets drawn, later calls do nothing.
+pub fn configure(opts: &Options) {
+    if CONFIGURED.get().is_some() {
+        return;
+    }
+    if let Some(p) = &opts.private_use {
+        let _ = PRIVATE_USE.set(p.clone());
+    }
+    let _ = CONFIGURED.set(Configured::new(opts));
+}
+
+fn configured() -> &'static Configured {
+    CONFIGURED.get_or_init(|| Configured::new(&Options::default()))
+}
+
+/// WHITE CIRCLE, which is East Asian Ambiguous and in pretty much every font.
+const AMBIGUOUS_PROBE: &str = "\u{25cb}";
+
+/// Find out whether the terminal draws East Asian Ambiguous characters wide,
+/// by printing one on the first row and seeing where the cursor ends up.
+/// Everything from that row to the end of the screen is cleared afterwards.
+pub fn probe_ambiguous(t: &mut Terminal) -> Result<AmbiguousWidth> {
+    Ok(match t.measure(1, AMBIGUOUS_PROBE)? {
+        2 => AmbiguousWidth::Wide,
+        _ => AmbiguousWidth::Narrow,
+    })
+}
+
+/// Every strategy, as set up by [`configure`], including the profile if one
+/// was loaded.
+pub fn strategies() -> Vec<Strategy> {
+    let c = configured();
+    let mut all = term_width_blog_example::STRATEGIES
+        .iter()
+        .map(|&(name, strat)| match name {
+            "unicode_width" => (name, &c.unicode_width as &dyn WidthStrategy),
+            "nfc_unicode_width" => (name, &c.nfc_unicode_width as &dyn WidthStrategy),
+            "widecharwidth_rec" => (name, &c.widecharwidth_rec as &dyn WidthStrategy),
+            "widecharwidth_fish" => (name, &c.widecharwidth_fish as &dyn WidthStrategy),
+            "termwiz_ish" => (name, &c.termwiz_ish as &dyn WidthStrategy),
+            "uts51" => (name, &c.uts51 as &dyn WidthStrategy),
+            "variation_selectors" => (name, &c.variation_selectors as &dyn WidthStrategy),
+            "hangul" => (name, &c.hangul as &dyn WidthStrategy),
+            "bidi" => (name, &c.bidi as &dyn WidthStrategy),
+            "mode2027" => (name, &c.mode2027 as &dyn WidthStrategy),
+            _ => (name, strat),
         })
-        .sum();
-    wrong_draw_common(t, x, y, &s, width)
+        .collect::<Vec<Strategy>>();
+    if let Some(p) = PROFILE.get() {
+        all.push(("profile", p));
+    }
+    all
+}
+
+static PROFILE: OnceLock<strategies::ProfileWidth> = OnceLock::new();
+
+/// Load the profile used by [`profile`]. Only the first call does anything.
+pub fn load_profile(path: &std::path::Path) -> Result<&'static strategies::ProfileWidth> {
+    if let Some(p) = PROFILE.get() {
+        return Ok(p);
+    }
+    let profile = Profile::load(path)
+        .map_err(|e| format!("couldn't load profile {}: {}", path.display(), e))?;
+    let mut strat = strategies::ProfileWidth::new(profile);
+    strat.fallback = configured().widecharwidth_rec;
+    Ok(PROFILE.get_or_init(|| strat))
+}
+
+pub fn profile(t: &mut Terminal, x: u16, y: u16, s: &str) -> Result<()> {
+    let strat = PROFILE.get().ok_or("no width profile loaded")?;
+    wrong_draw_common(t, x, y, s, strat)
 }
 
 pub fn read_pos(t: &mut Terminal, x: u16, y: u16, s: &str) -> Result<()> {
-    t.move_to(x, y + 1)?;
-    t.write_colored(BOX_COL, drawing::VLINE)?;
-    t.write(s)?;
-    t.write_colored(BOX_COL, drawing::VLINE)?;
-    let (ex, ey) = t.get_pos()?;
-    let (sx, sy) = (x, y + 1);
-    // Handle if we go off the end. In the sample code we can't, but the big
-    // downside of the approach is that you may have to handle that, since when
-    // you write some text, you won't know how long it will be.
-    let width = if ey != sy {
-        t.move_to(t.size().0 - 1, sy)?;
+    let lines = lines(s, None);
+    let mut width = 0;
+    for (sy, text) in (y + 1..).zip(&lines) {
+        let sx = x;
+        t.move_to(sx, sy)?;
         t.write_colored(BOX_COL, drawing::VLINE)?;
-        t.clear(Clear::ToEndOfScreen)?;
-        t.size().0.saturating_sub(sx)
-    } else {
-        debug_assert!(ex > sx);
-        ex - sx
-    };
-    let width = (width as usize).saturating_sub(2);
+        t.write(text)?;
+        let (ex, ey) = t.get_pos()?;
+        // Handle if we go off the end. In the sample code we can't, but the big
+        // downside of the approach is that you may have to handle that, since when
+        // you write some text, you won't know how long it will be.
+        let line_width = if ey != sy {
+            t.move_to(t.size().0 - 1, sy)?;
+            t.write_colored(BOX_COL, drawing::VLINE)?;
+            t.clear(Clear::ToEndOfScreen)?;
+            t.size().0.saturating_sub(sx)
+        } else {
+            debug_assert!(ex > sx);
+            ex - sx
+        };
+        width = width.max((line_width as usize).saturating_sub(1));
+    }
+    // Only now do we know where the right edge goes.
+    let right = x + 1 + width as u16;
+    for sy in (y + 1..).take(lines.len()) {
+        if right < t.size().0 {
+            t.move_to(right, sy)?;
+            t.write_colored(BOX_COL, drawing::VLINE)?;
+        }
+    }
     let line = drawing::HLINE.repeat(width);
     t.move_to(x, y)?;
     t.write_colored(
         BOX_COL,
         &format!("{}{}{}", drawing::CORNERS[0], line, drawing::CORNERS[1]),
     )?;
-    t.move_to(x, y + 2)?;
+    t.move_to(x, y + 1 + lines.len() as u16)?;
     t.write_colored(
         BOX_COL,
         &format!("{}{}{}", drawing::CORNERS[2], line, drawing::CORNERS[3]),
@@ -0,0 +1,104 @@
+//! Recognizing the emoji sequences from [UTS #51], and whether they're RGI
+//! ("recommended for general interchange", i.e. the ones fonts actually have
+//! a single glyph for).
+//!
+//! The grammar checks use `unic-emoji-char`'s properties, which are older than
+//! the RGI list, so sequences using newer emoji are only recognized when
+//! they're RGI.
+//!
+//! [UTS #51]: https://unicode.org/reports/tr51
+mod sequences;
+pub use sequences::EMOJI_VERSION;
+use unic_emoji_char::{is_emoji, is_emoji_modifier, is_emoji_modifier_base};
+
+pub const ZWJ: char = '\u{200d}';
+pub const VS15: char = '\u{fe0e}';
+pub const VS16: char = '\u{fe0f}';
+const KEYCAP: char = '\u{20e3}';
+const TAG_END: char = '\u{e007f}';
+
+/// The kinds of emoji sequence UTS #51 defines (ED-14 through ED-17).
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SequenceKind {
+    /// An emoji followed by VS16, e.g. `❤️`.
+    Presentation,
+    /// A modifier base followed by a skin tone, e.g. `👍🏽`.
+    Modifier,
+    /// A pair of regional indicators, e.g. `🇦🇶`.
+    Flag,
+    /// `[0-9#*]`, VS16, then U+20E3, e.g. `1️⃣`.
+    Keycap,
+    /// An emoji followed by tag characters, used for subdivision flags like
+    /// England's.
+    Tag,
+    /// Several of the above joined by U+200D, e.g. `👨‍👩‍👦‍👦`.
+    Zwj,
+}
+
+/// Whether `s` is one of the RGI emoji sequences. Single codepoints aren't
+/// included, use `unic_emoji_char::is_emoji_presentation` for those.
+pub fn is_rgi(s: &str) -> bool {
+    sequences::RGI_SEQUENCES.binary_search(&s).is_ok()
+}
+
+/// Whether `c` has both a text (VS15) and an emoji (VS16) variation sequence
+/// in emoji-variation-sequences.txt. Variation selectors after anything else
+/// are meaningless.
+pub fn has_variation_sequences(c: char) -> bool {
+    let c = c as u32;
+    let bases = &sequences::VARIATION_BASES;
+    let i = bases.partition_point(|&(_, hi)| hi < c);
+    bases.get(i).is_some_and(|&(lo, _)| lo <= c)
+}
+
+pub fn is_regional_indicator(c: char) -> bool {
+    ('\u{1f1e6}'..='\u{1f1ff}').contains(&c)
+}
+
+fn is_tag_spec(c: char) -> bool {
+    ('\u{e0020}'..='\u{e007e}').contains(&c)
+}
+
+// emoji_character | emoji_presentation_sequence | emoji_modifier_sequence,
+// which is what's allowed on either side of a ZWJ, or before tags.
+fn is_zwj_element(cs: &[char]) -> bool {
+    match *cs {
+        [c] => is_emoji(c),
+        [c, VS16] => is_emoji(c),
+        [b, m] => is_emoji_modifier_base(b) && is_emoji_modifier(m),
+        _ => false,
+    }
+}
+
+/// Which kind of emoji sequence `g` (usually one grapheme cluster) is, if any.
+/// This is purely the grammar, so `g` may or may not be RGI.
+pub fn classify(g: &str) -> Option<SequenceKind> {
+    let cs = g.chars().collect::<Vec<_>>();
+    if cs.contains(&ZWJ) {
+        let mut elements = cs.split(|&c| c == ZWJ);
+        let well_formed = cs.len() > 2 && elements.all(is_zwj_element);
+        return if well_formed || is_rgi(g) {
+            Some(SequenceKind::Zwj)
+        } else {
+            None
+        };
+    }
+    match cs[..] {
+        [a, b] if is_regional_indicator(a) && is_regional_indicator(b) => Some(SequenceKind::Flag),
+        ['0'..='9' | '#' | '*', VS16, KEYCAP] => Some(SequenceKind::Keycap),
+        [ref rest @ .., TAG_END] => {
+            let spec_start = rest.iter().position(|&c| is_tag_spec(c))?;
+            let (base, spec) = rest.split_at(spec_start);
+            if !spec.is_empty() && spec.iter().all(|&c| is_tag_spec(c)) && is_zwj_element(base) {
+                Some(SequenceKind::Tag)
+            } else {
+                None
+            }
+        }
+        [b, m] if is_emoji_modifier(m) && (is_emoji_modifier_base(b) || is_rgi(g)) => {
+            Some(SequenceKind::Modifier)
+        }
+        [c, VS16] if has_variation_sequences(c) || is_rgi(g) => Some(SequenceKind::Presentation),
+        _ => None,
+    }
+}
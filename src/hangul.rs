@@ -0,0 +1,69 @@
+//! Conjoining Hangul jamo, per section 3.12 of the Unicode standard.
+//!
+//! Korean text can be written as precomposed syllables (U+AC00 to U+D7A3), or
+//! as a sequence of leading consonant (L), vowel (V) and trailing consonant
+//! (T) jamo, which the font combines into a single syllable block. Either way
+//! the block is one wide glyph.
+
+/// The `Hangul_Syllable_Type` property.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SyllableType {
+    /// Leading consonant, U+1100 to U+115F and U+A960 to U+A97C.
+    L,
+    /// Vowel, U+1160 to U+11A7 and U+D7B0 to U+D7C6.
+    V,
+    /// Trailing consonant, U+11A8 to U+11FF and U+D7CB to U+D7FB.
+    T,
+    /// Precomposed syllable without a trailing consonant.
+    LV,
+    /// Precomposed syllable with a trailing consonant.
+    LVT,
+}
+
+const SYLLABLE_BASE: u32 = 0xAC00;
+const SYLLABLE_COUNT: u32 = 11172;
+const T_COUNT: u32 = 28;
+
+pub fn syllable_type(c: char) -> Option<SyllableType> {
+    let c = c as u32;
+    Some(match c {
+        0x1100..=0x115F | 0xA960..=0xA97C => SyllableType::L,
+        0x1160..=0x11A7 | 0xD7B0..=0xD7C6 => SyllableType::V,
+        0x11A8..=0x11FF | 0xD7CB..=0xD7FB => SyllableType::T,
+        _ if (SYLLABLE_BASE..SYLLABLE_BASE + SYLLABLE_COUNT).contains(&c) => {
+            if (c - SYLLABLE_BASE).is_multiple_of(T_COUNT) {
+                SyllableType::LV
+            } else {
+                SyllableType::LVT
+            }
+        }
+        _ => return None,
+    })
+}
+
+/// Length in bytes of the syllable block at the start of `s`, or 0 if it
+/// doesn't start with one.
+///
+/// This follows the grapheme rules (GB6 to GB8 in UAX #29), so `L+ V+ T*`, or
+/// the same with the `L V` or `L V T` part precomposed. Incomplete syllables,
+/// like a V or T with nothing before it, still get a block of their own, which
+/// is how the standard says to display them (as if padded with fillers).
+pub fn syllable_len(s: &str) -> usize {
+    use SyllableType::*;
+    let mut prev = None;
+    for (i, c) in s.char_indices() {
+        let next = match syllable_type(c) {
+            Some(next) => next,
+            None => return i,
+        };
+        let continues = matches!(
+            (prev, next),
+            (None, _) | (Some(L), L | V | LV | LVT) | (Some(V | LV), V | T) | (Some(T | LVT), T)
+        );
+        if !continues {
+            return i;
+        }
+        prev = Some(next);
+    }
+    s.len()
+}
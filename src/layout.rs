@@ -0,0 +1,106 @@
+//! Where each box goes. Every test draws its boxes for a phrase in the same
+//! column, so a column has to be as wide as the widest box any of them draws
+//! for it: the text itself, or the (possibly wrong) width a strategy guessed.
+use super::{draw, score::Strategy, term::Terminal, Result};
+use term_width_blog_example::{ansi, WidthStrategy};
+
+/// Blank columns between boxes.
+const GAP: usize = 3;
+
+/// How to find out how wide a phrase's text really is, for `--layout`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Reference {
+    /// Print it and ask the terminal where the cursor ended up.
+    Measure,
+    /// Trust the strategy picked by [`TerminalInfo::default_strategy`].
+    ///
+    /// [`TerminalInfo::default_strategy`]: crate::term::TerminalInfo::default_strategy
+    Auto,
+    /// Trust the strategy with this name.
+    Strategy(String),
+}
+
+impl std::str::FromStr for Reference {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, String> {
+        Ok(match s {
+            "measure" => Reference::Measure,
+            "auto" => Reference::Auto,
+            _ => Reference::Strategy(s.trim().to_ascii_lowercase().replace('-', "_")),
+        })
+    }
+}
+
+/// One row of boxes: each phrase, and the column its boxes start at.
+pub type Pass = Vec<(u16, String)>;
+
+/// How many columns the boxes for `phrase` can take up, borders included.
+/// `shown` has the strategy for each test being drawn, or `None` for one that
+/// doesn't have one (`read_pos`). With [`Reference::Measure`], this clears the
+/// first row of the screen.
+pub fn box_width(
+    t: &mut Terminal,
+    reference: &Reference,
+    strategies: &[Strategy],
+    shown: &[Option<&dyn WidthStrategy>],
+    phrase: &str,
+) -> Result<usize> {
+    let name = match reference {
+        Reference::Measure => None,
+        Reference::Auto => Some(t.info()?.default_strategy()),
+        Reference::Strategy(name) => Some(name.as_str()),
+    };
+    let reference = match name {
+        None => None,
+        Some(name) => Some(
+            strategies
+                .iter()
+                .find(|s| s.0 == name)
+                .ok_or_else(|| format!("no strategy named {:?} to lay out with", name))?
+                .1,
+        ),
+    };
+    let mut widest = 0;
+    let mut measured = std::collections::HashMap::new();
+    // Each test can break the phrase into lines differently.
+    for &strat in shown {
+        for line in draw::lines(phrase, strat) {
+            let visible = ansi::strip(&line);
+            let text = match reference {
+                None => match measured.get(&*line) {
+                    Some(&w) => w,
+                    None => {
+                        let w = t.measure(1, &line)?;
+                        measured.insert(line.to_string(), w);
+                        w
+                    }
+                },
+                Some(reference) => reference.str_width(&visible),
+            };
+            let guess = strat.map_or(0, |s| s.str_width(&visible));
+            widest = widest.max(text).max(guess);
+        }
+    }
+    Ok(widest + 2)
+}
+
+/// Split `phrases` (with their [`box_width`]s) into rows that fit in `cols`
+/// columns, starting at column `x`. A phrase too wide to fit anywhere still
+/// gets a row to itself.
+pub fn passes(phrases: &[(String, usize)], x: u16, cols: u16) -> Vec<Pass> {
+    let mut passes = vec![];
+    let mut cur = Pass::new();
+    let mut next_x = x as usize;
+    for (phrase, width) in phrases {
+        if next_x + width > cols as usize && !cur.is_empty() {
+            passes.push(std::mem::take(&mut cur));
+            next_x = x as usize;
+        }
+        cur.push((next_x as u16, phrase.clone()));
+        next_x += width + GAP;
+    }
+    if !cur.is_empty() {
+        passes.push(cur);
+    }
+    passes
+}
@@ -0,0 +1,94 @@
+//! The width measuring half of the example, split out from the drawing so it
+//! can be reused. Every approach the binary draws boxes with is a
+//! [`WidthStrategy`] in [`strategies`].
+pub mod ansi;
+pub mod bidi;
+pub mod emoji;
+pub mod hangul;
+pub mod pad;
+pub mod profile;
+pub mod strategies;
+pub mod truncate;
+pub mod wcwidths;
+pub mod wrap;
+
+/// Some way of guessing how many columns a terminal will use to display text.
+///
+/// Most of these are wrong in one way or another, which is sort of the point.
+pub trait WidthStrategy {
+    /// Width of `s` as a whole. Strategies that look at more than one
+    /// codepoint at a time (graphemes, normalization, ...) override this.
+    fn str_width(&self, s: &str) -> usize {
+        s.chars().map(|c| self.char_width(c)).sum()
+    }
+    /// Width of a lone `c`.
+    fn char_width(&self, c: char) -> usize;
+}
+
+/// Every strategy, along with the name the binary uses for it.
+pub const STRATEGIES: &[(&str, &dyn WidthStrategy)] = &[
+    ("byte_len", &strategies::ByteLen),
+    ("codepoints", &strategies::Codepoints),
+    ("nfc_codepoints", &strategies::NfcCodepoints),
+    ("graphemes", &strategies::Graphemes),
+    (
+        "unicode_width",
+        &strategies::UnicodeWidth {
+            ambiguous: wcwidths::AmbiguousWidth::Narrow,
+        },
+    ),
+    (
+        "nfc_unicode_width",
+        &strategies::NfcUnicodeWidth {
+            ambiguous: wcwidths::AmbiguousWidth::Narrow,
+        },
+    ),
+    ("system_wcwidth", &strategies::SystemWcwidth),
+    (
+        "widecharwidth_rec",
+        &strategies::WidecharwidthRecommended::LATEST,
+    ),
+    ("widecharwidth_fish", &strategies::WidecharwidthFish::LATEST),
+    (
+        "termwiz_ish",
+        &strategies::TermwizIsh {
+            ambiguous: wcwidths::AmbiguousWidth::Narrow,
+        },
+    ),
+    (
+        "uts51",
+        &strategies::Uts51 {
+            ambiguous: wcwidths::AmbiguousWidth::Narrow,
+        },
+    ),
+    (
+        "variation_selectors",
+        &strategies::VariationSelectors {
+            ignore: false,
+            ambiguous: wcwidths::AmbiguousWidth::Narrow,
+        },
+    ),
+    (
+        "hangul",
+        &strategies::HangulJamo {
+            ambiguous: wcwidths::AmbiguousWidth::Narrow,
+        },
+    ),
+    (
+        "bidi",
+        &strategies::Bidi {
+            ambiguous: wcwidths::AmbiguousWidth::Narrow,
+        },
+    ),
+    (
+        "mode2027",
+        &strategies::Mode2027 {
+            ambiguous: wcwidths::AmbiguousWidth::Narrow,
+        },
+    ),
+];
+
+/// Look up an entry in [`STRATEGIES`] by name.
+pub fn strategy(name: &str) -> Option<&'static dyn WidthStrategy> {
+    STRATEGIES.iter().find(|s| s.0 == name).map(|s| s.1)
+}
@@ -1,10 +1,18 @@
 #![allow(dead_code)]
 
+mod calibrate;
 mod draw;
+mod layout;
+mod report;
+mod score;
+mod table;
 mod term;
-mod wcwidths;
 use std::io::Write;
 use term::Terminal;
+use term_width_blog_example::{
+    wcwidths::{AmbiguousWidth, PrivateUseWidths, UnicodeVersion},
+    wrap,
+};
 type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
 type Result<T, E = Error> = std::result::Result<T, E>;
 
@@ -24,6 +32,12 @@ const IMPLS: &[DrawFunc] = &[
     ("widecharwidth_rec", draw::widecharwidth_recommended),
     ("widecharwidth_fish", draw::widecharwidth_fish),
     ("termwiz_ish", draw::termwiz_ish),
+    ("uts51", draw::uts51),
+    ("variation_selectors", draw::variation_selectors),
+    ("hangul", draw::hangul),
+    ("bidi", draw::bidi),
+    ("mode2027", draw::mode2027),
+    ("profile", draw::profile),
     ("read_pos", draw::read_pos),
 ];
 
@@ -33,6 +47,61 @@ fn list() {
     }
 }
 
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Format {
+    Boxes,
+    Json,
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "boxes" => Ok(Format::Boxes),
+            "json" => Ok(Format::Json),
+            _ => Err(format!(
+                "unknown format {:?}, expected `boxes` or `json`",
+                s
+            )),
+        }
+    }
+}
+
+/// What `--ambiguous` was given: a width, or `auto` to ask the terminal.
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Ambiguous {
+    Fixed(AmbiguousWidth),
+    Auto,
+}
+
+impl std::str::FromStr for Ambiguous {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "auto" => Ok(Ambiguous::Auto),
+            _ => s
+                .parse()
+                .map(Ambiguous::Fixed)
+                .map_err(|_| format!("unknown width {:?}, expected `narrow`, `wide` or `auto`", s)),
+        }
+    }
+}
+
+/// `on` or `off`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Toggle(bool);
+
+impl std::str::FromStr for Toggle {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "on" => Ok(Toggle(true)),
+            "off" => Ok(Toggle(false)),
+            _ => Err(format!("expected `on` or `off`, not {:?}", s)),
+        }
+    }
+}
+
 #[derive(argh::FromArgs)]
 /// Try to draw boxes around text.
 struct Args {
@@ -46,20 +115,115 @@ struct Args {
     #[argh(switch)]
     /// bypass isatty check
     force: bool,
+    /// milliseconds to wait for the terminal to answer each query before
+    /// giving up.
+    #[argh(option, default = "term::DEFAULT_TIMEOUT.as_millis() as u64")]
+    timeout: u64,
+    #[argh(switch)]
+    /// instead of drawing boxes, measure each phrase with the terminal and
+    /// print how far off each strategy was.
+    score: bool,
+    /// output format, either `boxes` (the default) or `json`, which measures
+    /// every phrase like `--score` and prints a machine-readable report.
+    #[argh(option, default = "Format::Boxes")]
+    format: Format,
+    /// how to find out how wide each phrase is when laying out the boxes:
+    /// `measure` (the default) to ask the terminal, `auto` for whichever test
+    /// suits the terminal (going by what it says it is), or the name of a
+    /// test.
+    #[argh(option, default = "layout::Reference::Measure")]
+    layout: layout::Reference,
+    /// break lines of a phrase wider than this many columns (by whichever
+    /// test is drawing it) between graphemes. phrases can also have their own
+    /// line breaks.
+    #[argh(option)]
+    max_width: Option<usize>,
+    /// word wrap each phrase to this many columns (by whichever test is
+    /// drawing it), at the places UAX #14 allows. overrides `--max-width`.
+    #[argh(option)]
+    wrap: Option<usize>,
+    /// with `--wrap`, indent every line after the first by this many columns.
+    #[argh(option, default = "0")]
+    hanging_indent: usize,
+    /// show each phrase cut down to this many columns (by whichever test is
+    /// drawing it) from the start, middle and end. overrides `--wrap` and
+    /// `--max-width`.
+    #[argh(option)]
+    truncate: Option<usize>,
+    #[argh(switch)]
+    /// rather than boxes, print each phrase left, center and right aligned by
+    /// each test, to check the padding lines up.
+    align: bool,
+    /// rather than boxes, print a table of how wide each test thinks each
+    /// phrase is, with the columns sized by the named test.
+    #[argh(option)]
+    table: Option<String>,
+    /// characters to draw `--table` with, `ascii` or `box-drawing`. defaults
+    /// to what the boxes use.
+    #[argh(option, default = "table::Border::default()")]
+    border: table::Border,
     #[argh(switch)]
     /// allow arguments longer than terminal width. only `read_pos` has been
     /// programmed to handle this, so you probably need `-t read-pos` too.
     allow_overlong: bool,
+    /// width profile written by `calibrate`, used by the `profile` test.
+    /// that test is skipped if this doesn't exist, unless asked for by `-t`.
+    #[argh(option, default = "std::path::PathBuf::from(\"width-profile.txt\")")]
+    profile: std::path::PathBuf,
+    /// version of unicode the widecharwidth tests should assume the terminal
+    /// implements, like `9.0`. defaults to the newest one they know about.
+    #[argh(option, default = "UnicodeVersion::LATEST")]
+    unicode_version: UnicodeVersion,
+    /// how wide the terminal draws East Asian Ambiguous characters, for every
+    /// test that measures them: `narrow` (the default), `wide`, or `auto` to
+    /// find out by printing one.
+    #[argh(option, default = "Ambiguous::Fixed(AmbiguousWidth::Narrow)")]
+    ambiguous: Ambiguous,
+    #[argh(switch)]
+    /// have the widecharwidth tests and `profile` treat Nerd Font icons as 2
+    /// wide and Powerline symbols as 1 wide, rather than all private use
+    /// characters as 1 wide.
+    nerd_fonts: bool,
+    /// file of width overrides for private use characters, like `e000-e00a 2`.
+    /// applied after `--nerd-fonts`.
+    #[argh(option)]
+    private_use: Option<std::path::PathBuf>,
+    /// turn the terminal's grapheme clustering mode (2027) `on` or `off` for
+    /// the whole run, rather than leaving it as it is. the `mode2027` test
+    /// always has it on. it's put back how it was afterwards.
+    #[argh(option)]
+    mode2027: Option<Toggle>,
+    #[argh(switch)]
+    /// make the `variation_selectors` test act like a terminal that ignores
+    /// VS15 and VS16, and always uses the default presentation.
+    ignore_variation_selectors: bool,
     /// only include the specified tests, may repeat, default is all
     #[argh(option, short = 't')]
     test: Vec<String>,
     /// list of phrases to draw boxed.
     #[argh(positional)]
     phrases: Vec<String>,
+    #[argh(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(argh::FromArgs)]
+#[argh(subcommand)]
+enum Command {
+    Calibrate(calibrate::Args),
+}
+
+fn open_terminal(args: &Args) -> Result<Terminal> {
+    let mut term = Terminal::open(true, args.no_color)?;
+    term.set_timeout(std::time::Duration::from_millis(args.timeout));
+    if let Some(Toggle(on)) = args.mode2027 {
+        term.set_mode(term::GRAPHEME_CLUSTERING, on)?;
+    }
+    Ok(term)
 }
 
 fn main() -> Result<()> {
-    crate::wcwidths::init_once();
+    term_width_blog_example::wcwidths::init_once();
     let mut args: Args = argh::from_env();
     if args.list {
         list();
@@ -73,63 +237,193 @@ fn main() -> Result<()> {
         std::process::exit(1);
     }
 
+    if let Some(Command::Calibrate(cal)) = &args.command {
+        let mut term = open_terminal(&args)?;
+        calibrate::run(&mut term, cal)?;
+        term.flush()?;
+        drop(term);
+        println!("wrote {}", cal.out.display());
+        return Ok(());
+    }
+
     let filters = args
         .test
         .iter()
         .map(|i| i.trim().to_ascii_lowercase().replace('-', "_"))
         .collect::<std::collections::HashSet<_>>();
 
-    let selected_tests = if filters.len() == 0 {
-        IMPLS.iter().collect()
-    } else {
-        IMPLS
-            .iter()
-            .filter(|f| filters.contains(f.0))
-            .collect::<Vec<_>>()
+    let ambiguous = match args.ambiguous {
+        Ambiguous::Fixed(w) => w,
+        Ambiguous::Auto => {
+            let mut term = open_terminal(&args)?;
+            let w = draw::probe_ambiguous(&mut term)?;
+            term.flush()?;
+            w
+        }
+    };
+    let mut private_use = args.nerd_fonts.then(PrivateUseWidths::nerd_fonts);
+    if let Some(path) = &args.private_use {
+        let overrides = PrivateUseWidths::load(path)
+            .map_err(|e| format!("couldn't load {}: {}", path.display(), e))?;
+        private_use
+            .get_or_insert_with(PrivateUseWidths::new)
+            .extend(&overrides);
+    }
+    draw::configure(&draw::Options {
+        unicode_version: args.unicode_version,
+        ambiguous,
+        private_use,
+        ignore_variation_selectors: args.ignore_variation_selectors,
+        max_width: args.max_width,
+        wrap: args.wrap.map(|width| wrap::Options {
+            width,
+            hanging_indent: args.hanging_indent,
+        }),
+        truncate: args.truncate,
+    });
+    let profile = match draw::load_profile(&args.profile) {
+        Ok(p) => Some(p),
+        Err(e) if filters.contains("profile") => return Err(e),
+        Err(_) => None,
     };
 
+    let selected_tests = IMPLS
+        .iter()
+        .filter(|f| filters.is_empty() || filters.contains(f.0))
+        .filter(|f| f.0 != "profile" || profile.is_some())
+        .collect::<Vec<_>>();
+
     if selected_tests.is_empty() && !filters.is_empty() {
         eprintln!("Warning: all implementations filtered. Printing options.");
         list();
         std::process::exit(1);
     }
 
-    let mut term = Terminal::open(true, args.no_color)?;
+    if args.align {
+        let strategies = draw::strategies();
+        let shown = strategies
+            .iter()
+            .filter(|s| selected_tests.iter().any(|t| t.0 == s.0))
+            .collect::<Vec<_>>();
+        print!("{}", draw::align_grid(&shown, &args.phrases));
+        return Ok(());
+    }
+
+    if let Some(name) = &args.table {
+        let name = name.trim().to_ascii_lowercase().replace('-', "_");
+        let strategies = draw::strategies();
+        let strat = strategies
+            .iter()
+            .find(|s| s.0 == name)
+            .ok_or_else(|| format!("no strategy named {:?} to lay out the table with", name))?
+            .1;
+        let shown = strategies
+            .iter()
+            .filter(|s| selected_tests.iter().any(|t| t.0 == s.0))
+            .collect::<Vec<_>>();
+        // The phrases get the same treatment the boxes would give them.
+        let phrase_column = match (args.truncate, args.wrap.or(args.max_width)) {
+            (Some(width), _) => table::Column {
+                max_width: Some(width),
+                overflow: table::Overflow::Truncate,
+                ..Default::default()
+            },
+            (None, width) => table::Column {
+                max_width: width,
+                ..Default::default()
+            },
+        };
+        let mut table = table::phrase_matrix(&args.phrases, &shown, phrase_column);
+        table.border = args.border;
+        for line in table.render(strat) {
+            println!("{}", line);
+        }
+        return Ok(());
+    }
+
+    let mut term = open_terminal(&args)?;
     let size = term.size();
 
+    if args.score || args.format == Format::Json {
+        let strategies = draw::strategies();
+        let strategies = strategies
+            .iter()
+            .filter(|s| filters.is_empty() || filters.contains(s.0))
+            .collect::<Vec<_>>();
+        let env = report::Env::current(&mut term);
+        term.clear(term::Clear::FullScreen)?;
+        let scores = score::score(&mut term, &args.phrases, &strategies)?;
+        term.move_to(1, 1)?;
+        term.flush()?;
+        drop(term);
+        if args.format == Format::Json {
+            println!("{}", report::json(&env, &scores));
+        } else {
+            score::print(&scores);
+        }
+        return Ok(());
+    }
+
     term.clear(term::Clear::FullScreen)?;
-    term.move_to(1, 1)?;
 
     // Ensure we won't try to make a column that goes off the end. If we would,
     // we just do set of rows.
-    let mut passes = vec![(0, vec![])];
-    for word in args.phrases {
-        let mut cur = passes.pop().unwrap();
-        if cur.0 + word.len() + 5 >= size.0 as usize && !cur.1.is_empty() {
-            passes.push(std::mem::replace(&mut cur, (0, vec![])));
-        }
-        cur.0 += word.len() + 4;
-        cur.1.push(word);
-        passes.push(cur)
+    let strategies = draw::strategies();
+    // `read_pos` doesn't have a strategy, so it gets `None`.
+    let strategy_for = |name: &str| strategies.iter().find(|s| s.0 == name).map(|s| s.1);
+    let shown = selected_tests
+        .iter()
+        .map(|t| strategy_for(t.0))
+        .collect::<Vec<_>>();
+    let mut widths = Vec::with_capacity(args.phrases.len());
+    for phrase in args.phrases {
+        let width = layout::box_width(&mut term, &args.layout, &strategies, &shown, &phrase)?;
+        widths.push((phrase, width));
     }
+    // A header on the first row, which stays put while the boxes scroll
+    // underneath it.
+    let header = match term.info() {
+        Ok(info) => info.to_string(),
+        Err(_) => "unknown terminal".to_string(),
+    };
+    let header = format!(
+        "{}: {} tests, {} phrases",
+        header,
+        selected_tests.len(),
+        widths.len()
+    );
+    term.move_to(1, 1)?;
+    term.clear(term::Clear::FullLine)?;
+    term.write_colored(term::Color::Cyan, &header)?;
+    let top = 2;
+    term.set_scroll_region(top, size.1)?;
+
+    let (x, mut y) = (1, top);
+    let passes = layout::passes(&widths, x, size.0);
+    term.move_to(x, y)?;
 
-    let (x, mut y) = (1, 1);
-    for (_, pass) in passes {
+    for pass in passes {
         for &test in &selected_tests {
+            let strat = strategy_for(test.0);
+            let lines = pass
+                .iter()
+                .map(|(_, word)| draw::lines(word, strat).len() as u16)
+                .max()
+                .unwrap_or(1);
+            // The label, then the boxes.
+            let rows = 1 + lines + 2;
             term.move_to(x, y)?;
-            if y + 4 >= size.1 {
-                term.scroll(4)?;
-                y -= 4;
+            if y + rows >= size.1 {
+                term.scroll(rows)?;
+                y = y.saturating_sub(rows).max(top);
                 term.move_to(x, y)?;
             }
             term.write_colored(term::Color::Yellow, test.0)?;
             y += 1;
-            let mut x = x;
-            for word in &pass {
-                (test.1)(&mut term, x, y, word)?;
-                x += word.len() as u16 + 5;
+            for (x, word) in &pass {
+                (test.1)(&mut term, *x, y, word)?;
             }
-            y += 3;
+            y += lines + 2;
             // term.move_to(x, y + 3)?;
         }
     }
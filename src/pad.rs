@@ -0,0 +1,100 @@
+//! Padding text out to a number of columns, for lining things up. The `std`
+//! formatting machinery (`{:>10}` and friends) pads by counting `char`s, which
+//! is wrong for the same reasons [`strategies::Codepoints`] is.
+//!
+//! [`strategies::Codepoints`]: crate::strategies::Codepoints
+use crate::{ansi, truncate, WidthStrategy};
+use std::borrow::Cow;
+use std::fmt;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+/// Pad `s` with spaces on the right to `width` columns, as measured by
+/// `strat`. Text that's already wide enough is left alone.
+pub fn pad_right<'a>(s: &'a str, width: usize, strat: &dyn WidthStrategy) -> Cow<'a, str> {
+    pad(s, width, Align::Left, strat)
+}
+
+/// Pad `s` with spaces on the left to `width` columns, see [`pad_right`].
+pub fn pad_left<'a>(s: &'a str, width: usize, strat: &dyn WidthStrategy) -> Cow<'a, str> {
+    pad(s, width, Align::Right, strat)
+}
+
+/// Pad `s` with spaces on both sides to `width` columns, see [`pad_right`].
+/// When the padding doesn't split evenly the extra space goes on the right,
+/// like `{:^}` does.
+pub fn pad_center<'a>(s: &'a str, width: usize, strat: &dyn WidthStrategy) -> Cow<'a, str> {
+    pad(s, width, Align::Center, strat)
+}
+
+/// Pad `s` to `width` columns so it ends up aligned as `align` says.
+pub fn pad<'a>(s: &'a str, width: usize, align: Align, strat: &dyn WidthStrategy) -> Cow<'a, str> {
+    let (before, after) = padding(strat.str_width(&ansi::strip(s)), width, align);
+    if before + after == 0 {
+        return Cow::Borrowed(s);
+    }
+    Cow::Owned(format!("{}{}{}", " ".repeat(before), s, " ".repeat(after)))
+}
+
+/// Columns of padding needed before and after something `used` wide.
+fn padding(used: usize, width: usize, align: Align) -> (usize, usize) {
+    let total = width.saturating_sub(used);
+    match align {
+        Align::Left => (0, total),
+        Align::Right => (total, 0),
+        Align::Center => (total / 2, total - total / 2),
+    }
+}
+
+/// Wraps `s` so `format!` pads it by display width. The width, fill and
+/// alignment all work the usual way (defaulting to left aligned, like strings
+/// do). A precision cuts it down to that many columns with
+/// [`truncate::truncate_end`], rather than that many `char`s. So
+/// `format!("{:>6}", padded("漢字", &UnicodeWidth::default()))` is `"  漢字"`.
+pub fn padded<'a>(s: &'a str, strat: &'a dyn WidthStrategy) -> Padded<'a> {
+    Padded { s, strat }
+}
+
+/// See [`padded`].
+#[derive(Copy, Clone)]
+pub struct Padded<'a> {
+    s: &'a str,
+    strat: &'a dyn WidthStrategy,
+}
+
+impl fmt::Display for Padded<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match f.precision() {
+            Some(max) => truncate::truncate_end(self.s, max, self.strat),
+            None => Cow::Borrowed(self.s),
+        };
+        let width = match f.width() {
+            Some(width) => width,
+            None => return f.write_str(&s),
+        };
+        let align = match f.align() {
+            Some(fmt::Alignment::Right) => Align::Right,
+            Some(fmt::Alignment::Center) => Align::Center,
+            Some(fmt::Alignment::Left) | None => Align::Left,
+        };
+        let (before, after) = padding(self.strat.str_width(&ansi::strip(&s)), width, align);
+        // A fill that's 2 wide only gets to fill an even number of columns,
+        // so make up the rest with spaces.
+        let fill = f.fill();
+        let fill_width = self.strat.char_width(fill).max(1);
+        let write_fill = |f: &mut fmt::Formatter<'_>, n: usize| -> fmt::Result {
+            for _ in 0..n / fill_width {
+                write!(f, "{}", fill)?;
+            }
+            f.write_str(&" ".repeat(n % fill_width))
+        };
+        write_fill(f, before)?;
+        f.write_str(&s)?;
+        write_fill(f, after)
+    }
+}
@@ -0,0 +1,254 @@
+//! Observed widths for a specific terminal, as recorded by the binary's
+//! `calibrate` subcommand.
+//!
+//! The file format is line based, and meant to be small enough to check in:
+//!
+//! ```text
+//! # comments start with '#'
+//! 20-7e 1
+//! 300 0
+//! seq 1f1e6 1f1f6 2
+//! ```
+//!
+//! That is, either a hex codepoint or inclusive range followed by the width,
+//! or `seq`, the hex codepoints of a grapheme sequence, and its width. Later
+//! lines win, and ranges don't need to be sorted or merged, although
+//! [`Profile`]'s `Display` impl writes them out that way.
+use std::collections::BTreeMap;
+use std::fmt;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Profile {
+    /// Sorted, non-overlapping, inclusive `(start, end, width)`. Adjacent
+    /// ranges with the same width are merged.
+    ranges: Vec<(u32, u32, usize)>,
+    sequences: BTreeMap<String, usize>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseProfileError {
+    /// 1-based.
+    pub line: usize,
+    pub msg: String,
+}
+
+impl fmt::Display for ParseProfileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "bad profile line {}: {}", self.line, self.msg)
+    }
+}
+
+impl std::error::Error for ParseProfileError {}
+
+fn parse_cp(s: &str) -> Result<u32, String> {
+    let n = u32::from_str_radix(s, 16).map_err(|e| format!("bad codepoint {:?}: {}", s, e))?;
+    if char::from_u32(n).is_none() {
+        return Err(format!("{:?} is not a valid codepoint", s));
+    }
+    Ok(n)
+}
+
+impl Profile {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn parse(src: &str) -> Result<Self, ParseProfileError> {
+        let mut profile = Self::new();
+        for (i, line) in src.lines().enumerate() {
+            let err = |msg: String| ParseProfileError { line: i + 1, msg };
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let mut fields = line.split_whitespace().collect::<Vec<_>>();
+            let width = fields
+                .pop()
+                .filter(|_| !fields.is_empty())
+                .ok_or_else(|| err("expected a codepoint and a width".into()))?;
+            let width = width
+                .parse::<usize>()
+                .map_err(|e| err(format!("bad width {:?}: {}", width, e)))?;
+            if fields[0] == "seq" {
+                let seq = fields[1..]
+                    .iter()
+                    .map(|f| parse_cp(f).map(|n| char::from_u32(n).unwrap()))
+                    .collect::<Result<String, _>>()
+                    .map_err(err)?;
+                if seq.is_empty() {
+                    return Err(err("empty sequence".into()));
+                }
+                profile.insert_sequence(&seq, width);
+            } else if fields.len() == 1 {
+                let (lo, hi) = match fields[0].split_once('-') {
+                    Some((lo, hi)) => (parse_cp(lo).map_err(err)?, parse_cp(hi).map_err(err)?),
+                    None => {
+                        let n = parse_cp(fields[0]).map_err(err)?;
+                        (n, n)
+                    }
+                };
+                if lo > hi {
+                    return Err(err(format!("backwards range {}", fields[0])));
+                }
+                profile.insert_range(lo, hi, width);
+            } else {
+                return Err(err(format!("unexpected {:?}", fields[1])));
+            }
+        }
+        Ok(profile)
+    }
+
+    pub fn load(
+        path: impl AsRef<std::path::Path>,
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(Self::parse(&std::fs::read_to_string(path)?)?)
+    }
+
+    /// Width recorded for `c`, if any.
+    pub fn char_width(&self, c: char) -> Option<usize> {
+        let c = c as u32;
+        let i = self.ranges.partition_point(|r| r.1 < c);
+        self.ranges.get(i).filter(|r| r.0 <= c).map(|r| r.2)
+    }
+
+    /// Width recorded for the grapheme sequence `s`, if any.
+    pub fn sequence_width(&self, s: &str) -> Option<usize> {
+        self.sequences.get(s).copied()
+    }
+
+    pub fn insert_char(&mut self, c: char, width: usize) {
+        self.insert_range(c as u32, c as u32, width);
+    }
+
+    pub fn insert_sequence(&mut self, s: &str, width: usize) {
+        self.sequences.insert(s.to_string(), width);
+    }
+
+    /// Record `width` for everything in `lo..=hi`, replacing whatever was
+    /// there before.
+    pub fn insert_range(&mut self, lo: u32, hi: u32, width: usize) {
+        debug_assert!(lo <= hi);
+        // Everything that overlaps or touches the new range gets pulled out,
+        // and whatever pieces of it stick out either side get put back.
+        let start = self.ranges.partition_point(|r| r.1.saturating_add(1) < lo);
+        let end = self.ranges.partition_point(|r| r.0 <= hi.saturating_add(1));
+        let mut replacement = Vec::with_capacity(3);
+        let (mut lo, mut hi) = (lo, hi);
+        for &(rlo, rhi, rw) in &self.ranges[start..end] {
+            if rw == width {
+                lo = lo.min(rlo);
+                hi = hi.max(rhi);
+                continue;
+            }
+            if rlo < lo {
+                replacement.push((rlo, lo - 1, rw));
+            }
+            if rhi > hi {
+                replacement.push((hi + 1, rhi, rw));
+            }
+        }
+        replacement.push((lo, hi, width));
+        replacement.sort_unstable();
+        self.ranges.splice(start..end, replacement);
+    }
+
+    /// Iterate over the `(start, end, width)` ranges, in order.
+    pub fn ranges(&self) -> impl Iterator<Item = (u32, u32, usize)> + '_ {
+        self.ranges.iter().copied()
+    }
+
+    /// Iterate over the recorded grapheme sequences and their widths.
+    pub fn sequences(&self) -> impl Iterator<Item = (&str, usize)> + '_ {
+        self.sequences.iter().map(|(s, &w)| (s.as_str(), w))
+    }
+}
+
+/// Write out a single range as a profile line.
+pub fn range_line(lo: u32, hi: u32, width: usize) -> String {
+    if lo == hi {
+        format!("{:x} {}", lo, width)
+    } else {
+        format!("{:x}-{:x} {}", lo, hi, width)
+    }
+}
+
+/// Write out a single sequence as a profile line.
+pub fn sequence_line(s: &str, width: usize) -> String {
+    let mut out = "seq".to_string();
+    for c in s.chars() {
+        out.push_str(&format!(" {:x}", c as u32));
+    }
+    out.push_str(&format!(" {}", width));
+    out
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for &(lo, hi, w) in &self.ranges {
+            writeln!(f, "{}", range_line(lo, hi, w))?;
+        }
+        for (s, &w) in &self.sequences {
+            writeln!(f, "{}", sequence_line(s, w))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ranges(inserts: &[(u32, u32, usize)]) -> Vec<(u32, u32, usize)> {
+        let mut p = Profile::new();
+        for &(lo, hi, width) in inserts {
+            p.insert_range(lo, hi, width);
+        }
+        p.ranges().collect()
+    }
+
+    #[test]
+    fn separate() {
+        assert_eq!(ranges(&[(10, 20, 2), (0, 5, 2)]), [(0, 5, 2), (10, 20, 2)]);
+    }
+
+    #[test]
+    fn merges_same_width() {
+        // Touching.
+        assert_eq!(ranges(&[(0, 4, 2), (5, 9, 2)]), [(0, 9, 2)]);
+        // Overlapping.
+        assert_eq!(ranges(&[(0, 6, 2), (4, 9, 2)]), [(0, 9, 2)]);
+        // Filling the gap between two.
+        assert_eq!(ranges(&[(0, 3, 1), (7, 9, 1), (4, 6, 1)]), [(0, 9, 1)]);
+        // Covering several.
+        assert_eq!(ranges(&[(2, 3, 0), (5, 6, 0), (0, 9, 0)]), [(0, 9, 0)]);
+    }
+
+    #[test]
+    fn touching_different_width() {
+        assert_eq!(ranges(&[(0, 4, 1), (5, 9, 2)]), [(0, 4, 1), (5, 9, 2)]);
+    }
+
+    #[test]
+    fn replaces_different_width() {
+        // Splits the old range in two.
+        assert_eq!(
+            ranges(&[(0, 9, 1), (4, 5, 2)]),
+            [(0, 3, 1), (4, 5, 2), (6, 9, 1)]
+        );
+        // Cuts the ends off the ones either side, and replaces the middle.
+        assert_eq!(
+            ranges(&[(0, 3, 1), (4, 5, 0), (6, 9, 1), (2, 7, 2)]),
+            [(0, 1, 1), (2, 7, 2), (8, 9, 1)]
+        );
+        // Putting the middle back joins them up again.
+        assert_eq!(ranges(&[(0, 9, 1), (4, 5, 2), (4, 5, 1)]), [(0, 9, 1)]);
+    }
+
+    #[test]
+    fn extremes() {
+        assert_eq!(
+            ranges(&[(0, u32::MAX, 1), (u32::MAX, u32::MAX, 2)]),
+            [(0, u32::MAX - 1, 1), (u32::MAX, u32::MAX, 2)]
+        );
+    }
+}
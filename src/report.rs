@@ -0,0 +1,104 @@
+//! `--format json`: the same data as `--score`, plus enough about the
+//! environment to tell results from different terminals apart.
+use super::{
+    score::Score,
+    term::{Terminal, TerminalInfo},
+};
+use std::fmt::Write;
+
+pub struct Env {
+    pub term: Option<String>,
+    pub term_program: Option<String>,
+    pub locale: Option<String>,
+    /// `(columns, rows)`
+    pub size: (u16, u16),
+    /// `None` if the terminal didn't answer.
+    pub terminal: Option<TerminalInfo>,
+}
+
+impl Env {
+    pub fn current(t: &mut Terminal) -> Self {
+        Self {
+            term: std::env::var("TERM").ok(),
+            term_program: std::env::var("TERM_PROGRAM").ok(),
+            locale: term_width_blog_example::wcwidths::locale(),
+            size: t.size(),
+            terminal: t.info().ok(),
+        }
+    }
+}
+
+// Not worth pulling in serde for this.
+fn string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+fn opt_string(out: &mut String, s: Option<&str>) {
+    match s {
+        Some(s) => string(out, s),
+        None => out.push_str("null"),
+    }
+}
+
+pub fn json(env: &Env, scores: &[Score]) -> String {
+    let mut out = String::new();
+    out.push_str("{\n  \"term\": ");
+    opt_string(&mut out, env.term.as_deref());
+    out.push_str(",\n  \"term_program\": ");
+    opt_string(&mut out, env.term_program.as_deref());
+    out.push_str(",\n  \"locale\": ");
+    opt_string(&mut out, env.locale.as_deref());
+    let _ = write!(
+        out,
+        ",\n  \"size\": {{\"columns\": {}, \"rows\": {}}}",
+        env.size.0, env.size.1,
+    );
+    out.push_str(",\n  \"terminal\": ");
+    match &env.terminal {
+        Some(info) => {
+            out.push_str("{\"name\": ");
+            opt_string(&mut out, info.name.as_deref());
+            out.push_str(", \"version\": ");
+            opt_string(&mut out, info.version.as_deref());
+            match info.id {
+                Some(id) => {
+                    let _ = write!(out, ", \"id\": {}", id);
+                }
+                None => out.push_str(", \"id\": null"),
+            }
+            let _ = write!(out, ", \"features\": {:?}}}", info.features);
+        }
+        None => out.push_str("null"),
+    }
+    out.push_str(",\n  \"phrases\": [");
+    for (i, score) in scores.iter().enumerate() {
+        out.push_str(if i == 0 { "\n" } else { ",\n" });
+        out.push_str("    {\"phrase\": ");
+        string(&mut out, &score.phrase);
+        let _ = write!(out, ", \"measured\": {}, \"widths\": {{", score.actual);
+        for (j, &(name, width)) in score.guesses.iter().enumerate() {
+            if j != 0 {
+                out.push_str(", ");
+            }
+            string(&mut out, name);
+            let _ = write!(out, ": {}", width);
+        }
+        out.push_str("}}");
+    }
+    out.push_str("\n  ]\n}");
+    out
+}
@@ -0,0 +1,97 @@
+//! `--score`: rather than eyeballing the boxes, ask the terminal how wide each
+//! phrase really is (the same trick `draw::read_pos` uses) and compare every
+//! strategy against that.
+use super::{term::Terminal, Result};
+use term_width_blog_example::{ansi, WidthStrategy};
+
+pub type Strategy = (&'static str, &'static dyn WidthStrategy);
+
+pub struct Score {
+    pub phrase: String,
+    /// What the terminal actually did.
+    pub actual: usize,
+    /// What each strategy guessed, in the same order as they were passed in.
+    pub guesses: Vec<(&'static str, usize)>,
+}
+
+pub fn score(t: &mut Terminal, phrases: &[String], strategies: &[&Strategy]) -> Result<Vec<Score>> {
+    let mut scores = Vec::with_capacity(phrases.len());
+    for phrase in phrases {
+        let actual = t.measure(1, phrase)?;
+        // Escapes go to the terminal, but aren't something to measure.
+        let visible = ansi::strip(phrase);
+        let guesses = strategies
+            .iter()
+            .map(|(name, strat)| (*name, strat.str_width(&visible)))
+            .collect();
+        scores.push(Score {
+            phrase: phrase.clone(),
+            actual,
+            guesses,
+        });
+    }
+    Ok(scores)
+}
+
+fn verdict(guess: usize, actual: usize) -> String {
+    use std::cmp::Ordering::*;
+    match guess.cmp(&actual) {
+        Equal => "ok".to_string(),
+        Greater => format!("over by {}", guess - actual),
+        Less => format!("under by {}", actual - guess),
+    }
+}
+
+/// Print the results to stdout. Needs to happen after the terminal has been
+/// closed, since it's in raw mode until then.
+pub fn print(scores: &[Score]) {
+    let name_width = scores
+        .iter()
+        .flat_map(|s| s.guesses.iter().map(|g| g.0.len()))
+        .max()
+        .unwrap_or_default();
+    for score in scores {
+        // Debug formatting so that the invisible stuff is visible.
+        println!("{:?}: terminal says {}", score.phrase, score.actual);
+        for &(name, guess) in &score.guesses {
+            println!(
+                "    {:<w$}  {:>3}  {}",
+                name,
+                guess,
+                verdict(guess, score.actual),
+                w = name_width,
+            );
+        }
+    }
+    let Some(first) = scores.first() else {
+        return;
+    };
+    println!();
+    println!("summary ({} phrases):", scores.len());
+    let mut totals = first
+        .guesses
+        .iter()
+        .enumerate()
+        .map(|(i, &(name, _))| {
+            let (exact, error) = scores.iter().fold((0, 0), |(exact, error), s| {
+                let guess = s.guesses[i].1;
+                (
+                    exact + (guess == s.actual) as usize,
+                    error + guess.abs_diff(s.actual),
+                )
+            });
+            (name, exact, error)
+        })
+        .collect::<Vec<_>>();
+    totals.sort_by_key(|&(_, exact, error)| (std::cmp::Reverse(exact), error));
+    for (name, exact, error) in totals {
+        println!(
+            "    {:<w$}  {:>3}/{} exact, off by {} in total",
+            name,
+            exact,
+            scores.len(),
+            error,
+            w = name_width,
+        );
+    }
+}
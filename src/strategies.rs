@@ -0,0 +1,548 @@
+//! The approaches the blog post walks through, from worst to least bad.
+use super::{
+    bidi, emoji, hangul,
+    profile::Profile,
+    wcwidths::{self, AmbiguousWidth, PrivateUseWidths, UnicodeVersion},
+    WidthStrategy,
+};
+use unicode_normalization::UnicodeNormalization;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// `str::len`. Only right for ASCII.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct ByteLen;
+
+impl WidthStrategy for ByteLen {
+    fn str_width(&self, s: &str) -> usize {
+        s.len()
+    }
+    fn char_width(&self, c: char) -> usize {
+        c.len_utf8()
+    }
+}
+
+/// Number of `char`s.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Codepoints;
+
+impl WidthStrategy for Codepoints {
+    fn char_width(&self, _: char) -> usize {
+        1
+    }
+}
+
+/// Number of `char`s after NFC normalization, which fixes some (but only
+/// some) combining characters.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct NfcCodepoints;
+
+impl WidthStrategy for NfcCodepoints {
+    fn str_width(&self, s: &str) -> usize {
+        s.nfc().count()
+    }
+    fn char_width(&self, _: char) -> usize {
+        1
+    }
+}
+
+/// Number of extended grapheme clusters.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Graphemes;
+
+impl WidthStrategy for Graphemes {
+    fn str_width(&self, s: &str) -> usize {
+        s.graphemes(true).count()
+    }
+    fn char_width(&self, _: char) -> usize {
+        1
+    }
+}
+
+/// `unicode-width`'s width for `s`, using its CJK widths if ambiguous
+/// characters are wide.
+fn unicode_width(s: &str, ambiguous: AmbiguousWidth) -> usize {
+    match ambiguous {
+        AmbiguousWidth::Narrow => s.width(),
+        AmbiguousWidth::Wide => s.width_cjk(),
+    }
+}
+
+/// [`unicode_width`] for a lone `c`.
+fn unicode_char_width(c: char, ambiguous: AmbiguousWidth) -> usize {
+    match ambiguous {
+        AmbiguousWidth::Narrow => c.width(),
+        AmbiguousWidth::Wide => c.width_cjk(),
+    }
+    .unwrap_or_default()
+}
+
+/// The `unicode-width` crate.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct UnicodeWidth {
+    /// How wide the terminal makes East Asian Ambiguous characters.
+    pub ambiguous: AmbiguousWidth,
+}
+
+impl WidthStrategy for UnicodeWidth {
+    fn str_width(&self, s: &str) -> usize {
+        unicode_width(s, self.ambiguous)
+    }
+    fn char_width(&self, c: char) -> usize {
+        unicode_char_width(c, self.ambiguous)
+    }
+}
+
+/// The `unicode-width` crate, after NFC normalization.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct NfcUnicodeWidth {
+    /// How wide the terminal makes East Asian Ambiguous characters.
+    pub ambiguous: AmbiguousWidth,
+}
+
+impl WidthStrategy for NfcUnicodeWidth {
+    fn str_width(&self, s: &str) -> usize {
+        unicode_width(&s.nfc().collect::<String>(), self.ambiguous)
+    }
+    fn char_width(&self, c: char) -> usize {
+        unicode_char_width(c, self.ambiguous)
+    }
+}
+
+/// libc's `wcwidth`, with nonprinting characters counted as 0.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct SystemWcwidth;
+
+impl WidthStrategy for SystemWcwidth {
+    fn char_width(&self, c: char) -> usize {
+        wcwidths::init_once();
+        wcwidths::system_wcwidth(c).unwrap_or_default()
+    }
+}
+
+/// See [`wcwidths::widecharwidth_recommended`].
+#[derive(Copy, Clone, Debug, Default)]
+pub struct WidecharwidthRecommended {
+    /// Answer as a terminal that implements this version of Unicode would.
+    pub version: UnicodeVersion,
+    /// How wide the terminal makes East Asian Ambiguous characters.
+    pub ambiguous: AmbiguousWidth,
+    /// Widths for private use characters, rather than 1.
+    pub private_use: Option<&'static PrivateUseWidths>,
+}
+
+impl WidecharwidthRecommended {
+    pub const LATEST: Self = Self {
+        version: UnicodeVersion::LATEST,
+        ambiguous: AmbiguousWidth::Narrow,
+        private_use: None,
+    };
+}
+
+impl WidthStrategy for WidecharwidthRecommended {
+    fn char_width(&self, c: char) -> usize {
+        if let Some(w) = self.private_use.and_then(|p| p.width(c)) {
+            return w;
+        }
+        wcwidths::widecharwidth_recommended_in(c, self.version, self.ambiguous)
+    }
+}
+
+/// See [`wcwidths::widecharwidth_fish`].
+#[derive(Copy, Clone, Debug, Default)]
+pub struct WidecharwidthFish {
+    /// Answer as a terminal that implements this version of Unicode would.
+    pub version: UnicodeVersion,
+    /// How wide the terminal makes East Asian Ambiguous characters.
+    pub ambiguous: AmbiguousWidth,
+    /// Widths for private use characters, rather than 1.
+    pub private_use: Option<&'static PrivateUseWidths>,
+}
+
+impl WidecharwidthFish {
+    pub const LATEST: Self = Self {
+        version: UnicodeVersion::LATEST,
+        ambiguous: AmbiguousWidth::Narrow,
+        private_use: None,
+    };
+}
+
+impl WidthStrategy for WidecharwidthFish {
+    fn char_width(&self, c: char) -> usize {
+        if let Some(w) = self.private_use.and_then(|p| p.width(c)) {
+            return w;
+        }
+        wcwidths::init_once();
+        wcwidths::widecharwidth_fish_in(c, self.version, self.ambiguous)
+    }
+}
+
+/// Roughly what termwiz does: measure NFC graphemes, and treat anything that
+/// looks like an emoji sequence as 2 wide.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct TermwizIsh {
+    /// How wide the terminal makes East Asian Ambiguous characters.
+    pub ambiguous: AmbiguousWidth,
+}
+
+impl WidthStrategy for TermwizIsh {
+    fn str_width(&self, s: &str) -> usize {
+        let s = s.nfc().collect::<String>();
+        s.graphemes(true).map(|g| self.grapheme_width(g)).sum()
+    }
+    fn char_width(&self, c: char) -> usize {
+        self.grapheme_width(c.encode_utf8(&mut [0; 4]))
+    }
+}
+
+impl TermwizIsh {
+    fn grapheme_width(&self, g: &str) -> usize {
+        let is_emoji_sequence = g.chars().any(|c| {
+            // This is incomplete, but you could imagine a version which
+            // follows https://unicode.org/reports/tr51.
+            unic_emoji_char::is_emoji_modifier(c)
+                || unic_emoji_char::is_emoji_modifier_base(c)
+                // regional indicator sequence.
+                || (0x1F1E6..=0x1F1FF).contains(&(c as u32))
+        });
+        if is_emoji_sequence {
+            2
+        } else {
+            unicode_width(g, self.ambiguous)
+        }
+    }
+}
+
+/// What termwiz-ish is imagining: split into graphemes, and measure the ones
+/// that are emoji sequences according to [UTS #51]. RGI sequences are a single
+/// wide glyph, the rest are drawn as their parts (or, for tag sequences, just
+/// the base).
+///
+/// [UTS #51]: https://unicode.org/reports/tr51
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Uts51 {
+    /// How wide the terminal makes East Asian Ambiguous characters.
+    pub ambiguous: AmbiguousWidth,
+}
+
+impl Uts51 {
+    /// The width of one grapheme cluster.
+    pub fn grapheme_width(&self, g: &str) -> usize {
+        use emoji::SequenceKind;
+        let rgi = emoji::is_rgi(g);
+        let mut chars = g.chars();
+        let first = match chars.next() {
+            Some(c) => c,
+            None => return 0,
+        };
+        match emoji::classify(g) {
+            Some(SequenceKind::Flag | SequenceKind::Keycap | SequenceKind::Presentation) => 2,
+            Some(SequenceKind::Modifier) if rgi => 2,
+            // Unsupported modifier sequences show the base, then a color swatch.
+            Some(SequenceKind::Modifier) => self.char_width(first) + 2,
+            Some(SequenceKind::Tag | SequenceKind::Zwj) if rgi => 2,
+            // The tag characters are invisible, so it's just the base.
+            Some(SequenceKind::Tag) => {
+                let base = g.find(|c| ('\u{e0020}'..='\u{e007f}').contains(&c));
+                self.grapheme_width(&g[..base.unwrap_or(g.len())])
+            }
+            Some(SequenceKind::Zwj) => g.split(emoji::ZWJ).map(|e| self.grapheme_width(e)).sum(),
+            None => match (chars.next(), chars.next()) {
+                (Some(emoji::VS15), None) if unic_emoji_char::is_emoji(first) => 1,
+                (None, _) => self.char_width(first),
+                _ => unicode_width(g, self.ambiguous),
+            },
+        }
+    }
+}
+
+impl WidthStrategy for Uts51 {
+    fn str_width(&self, s: &str) -> usize {
+        s.graphemes(true).map(|g| self.grapheme_width(g)).sum()
+    }
+    fn char_width(&self, c: char) -> usize {
+        if unic_emoji_char::is_emoji_presentation(c) {
+            2
+        } else {
+            unicode_char_width(c, self.ambiguous)
+        }
+    }
+}
+
+/// [`Uts51`] per grapheme, except that a variation selector switches its base
+/// between text (1 wide) and emoji (2 wide) presentation. That only applies to
+/// characters emoji-variation-sequences.txt lists, anywhere else the selector
+/// is ignored.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct VariationSelectors {
+    /// Act like a terminal that ignores variation selectors, so everything
+    /// gets its default presentation.
+    pub ignore: bool,
+    /// How wide the terminal makes East Asian Ambiguous characters.
+    pub ambiguous: AmbiguousWidth,
+}
+
+impl VariationSelectors {
+    fn uts51(&self) -> Uts51 {
+        Uts51 {
+            ambiguous: self.ambiguous,
+        }
+    }
+
+    /// The width of one grapheme cluster.
+    pub fn grapheme_width(&self, g: &str) -> usize {
+        let mut chars = g.chars();
+        match (chars.next(), chars.next(), chars.as_str()) {
+            // Just the base and its selector, or a keycap. Anything longer
+            // (say, a ZWJ sequence that starts with one) is up to `Uts51`.
+            (Some(base), Some(vs @ (emoji::VS15 | emoji::VS16)), "" | "\u{20e3}")
+                if !self.ignore && emoji::has_variation_sequences(base) =>
+            {
+                if vs == emoji::VS16 {
+                    2
+                } else {
+                    1
+                }
+            }
+            // Without the selectors, so `Uts51` doesn't look at them either.
+            _ if self.ignore => self
+                .uts51()
+                .grapheme_width(&g.replace([emoji::VS15, emoji::VS16], "")),
+            _ => self.uts51().grapheme_width(g),
+        }
+    }
+}
+
+impl WidthStrategy for VariationSelectors {
+    fn str_width(&self, s: &str) -> usize {
+        s.graphemes(true).map(|g| self.grapheme_width(g)).sum()
+    }
+    fn char_width(&self, c: char) -> usize {
+        // The default presentation.
+        self.uts51().char_width(c)
+    }
+}
+
+/// What a terminal does with the grapheme clustering mode (DEC private mode
+/// 2027) turned on, going by [the spec]: each grapheme cluster is as wide as
+/// its first character, unless a variation selector picks the other
+/// presentation for it.
+///
+/// [the spec]: https://github.com/contour-terminal/terminal-unicode-core
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Mode2027 {
+    /// How wide the terminal makes East Asian Ambiguous characters.
+    pub ambiguous: AmbiguousWidth,
+}
+
+impl Mode2027 {
+    /// The width of one grapheme cluster.
+    pub fn grapheme_width(&self, g: &str) -> usize {
+        let first = match g.chars().next() {
+            Some(c) => c,
+            None => return 0,
+        };
+        if emoji::has_variation_sequences(first) {
+            if g.contains(emoji::VS16) {
+                return 2;
+            } else if g.contains(emoji::VS15) {
+                return 1;
+            }
+        }
+        self.char_width(first)
+    }
+}
+
+impl WidthStrategy for Mode2027 {
+    fn str_width(&self, s: &str) -> usize {
+        s.graphemes(true).map(|g| self.grapheme_width(g)).sum()
+    }
+    fn char_width(&self, c: char) -> usize {
+        Uts51 {
+            ambiguous: self.ambiguous,
+        }
+        .char_width(c)
+    }
+}
+
+/// `unicode-width`, except that each Hangul syllable block is one wide cell,
+/// however many conjoining jamo it's spelled with. See [`hangul`].
+#[derive(Copy, Clone, Debug, Default)]
+pub struct HangulJamo {
+    /// How wide the terminal makes East Asian Ambiguous characters.
+    pub ambiguous: AmbiguousWidth,
+}
+
+impl WidthStrategy for HangulJamo {
+    fn str_width(&self, s: &str) -> usize {
+        // `unicode-width` measures the text between syllable blocks, so it
+        // still gets to see emoji sequences and the like whole.
+        let mut width = 0;
+        let mut run_start = 0;
+        let mut rest = s;
+        while let Some(c) = rest.chars().next() {
+            let start = s.len() - rest.len();
+            match hangul::syllable_len(rest) {
+                0 => rest = &rest[c.len_utf8()..],
+                len => {
+                    width += unicode_width(&s[run_start..start], self.ambiguous) + 2;
+                    rest = &rest[len..];
+                    run_start = s.len() - rest.len();
+                }
+            }
+        }
+        width + unicode_width(&s[run_start..], self.ambiguous)
+    }
+    fn char_width(&self, c: char) -> usize {
+        if hangul::syllable_type(c).is_some() {
+            2
+        } else {
+            unicode_char_width(c, self.ambiguous)
+        }
+    }
+}
+
+/// [`Uts51`], but measuring each of the runs [`bidi::visual_runs`] splits a
+/// line into separately, the way a terminal that does bidi lays them out.
+/// Nothing gets clustered across a change of direction.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Bidi {
+    /// How wide the terminal makes East Asian Ambiguous characters.
+    pub ambiguous: AmbiguousWidth,
+}
+
+impl WidthStrategy for Bidi {
+    fn str_width(&self, s: &str) -> usize {
+        let uts51 = Uts51 {
+            ambiguous: self.ambiguous,
+        };
+        s.split('\n')
+            .flat_map(bidi::visual_runs)
+            .map(|run| uts51.str_width(run.text))
+            .sum()
+    }
+    fn char_width(&self, c: char) -> usize {
+        Uts51 {
+            ambiguous: self.ambiguous,
+        }
+        .char_width(c)
+    }
+}
+
+/// Widths recorded from an actual terminal by the `calibrate` subcommand. Any
+/// character or grapheme the [`Profile`] doesn't cover falls back to
+/// [`WidecharwidthRecommended`].
+#[derive(Clone, Debug, Default)]
+pub struct ProfileWidth {
+    profile: Profile,
+    /// What to use for characters the profile doesn't have.
+    pub fallback: WidecharwidthRecommended,
+}
+
+impl ProfileWidth {
+    pub fn new(profile: Profile) -> Self {
+        Self {
+            profile,
+            fallback: WidecharwidthRecommended::LATEST,
+        }
+    }
+
+    pub fn profile(&self) -> &Profile {
+        &self.profile
+    }
+}
+
+impl WidthStrategy for ProfileWidth {
+    fn str_width(&self, s: &str) -> usize {
+        s.graphemes(true)
+            .map(|g| {
+                self.profile
+                    .sequence_width(g)
+                    .unwrap_or_else(|| g.chars().map(|c| self.char_width(c)).sum())
+            })
+            .sum()
+    }
+    fn char_width(&self, c: char) -> usize {
+        self.profile
+            .char_width(c)
+            .unwrap_or_else(|| self.fallback.char_width(c))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn variation_selectors() {
+        let vs = VariationSelectors::default();
+        // Emoji by default, with a text style.
+        assert_eq!(vs.str_width("\u{231a}"), 2);
+        assert_eq!(vs.str_width("\u{231a}\u{fe0e}"), 1);
+        assert_eq!(vs.str_width("\u{2b50}\u{fe0e}"), 1);
+        assert_eq!(vs.str_width("\u{2615}\u{fe0e}"), 1);
+        assert_eq!(vs.str_width("\u{1f004}\u{fe0e}"), 1);
+        // Text by default, with an emoji style.
+        assert_eq!(vs.str_width("\u{2764}"), 1);
+        assert_eq!(vs.str_width("\u{2764}\u{fe0f}"), 2);
+        // Not a variation base, so the selector does nothing.
+        assert_eq!(vs.str_width("a\u{fe0f}"), 1);
+
+        let ignore = VariationSelectors {
+            ignore: true,
+            ..VariationSelectors::default()
+        };
+        assert_eq!(ignore.str_width("\u{231a}\u{fe0e}"), 2);
+        assert_eq!(ignore.str_width("\u{2764}\u{fe0f}"), 1);
+    }
+
+    #[test]
+    fn variation_selectors_whole_graphemes() {
+        let vs = VariationSelectors::default();
+        let family = "\u{1f468}\u{200d}\u{1f469}\u{200d}\u{1f467}";
+        assert_eq!(vs.str_width(family), 2);
+        assert_eq!(vs.str_width("\u{1f1ef}\u{1f1f5}"), 2);
+        assert_eq!(vs.str_width("\u{1f44d}\u{1f3fd}"), 2);
+        // Starts with a variation sequence, but it's part of a ZWJ sequence.
+        assert_eq!(vs.str_width("\u{1f3f3}\u{fe0f}\u{200d}\u{1f308}"), 2);
+    }
+
+    #[test]
+    fn uts51_zwj_sequences() {
+        let uts51 = Uts51::default();
+        // RGI, including since Emoji 13.1.
+        assert_eq!(uts51.str_width("\u{1f469}\u{200d}\u{1f4bb}"), 2);
+        assert_eq!(uts51.str_width("\u{2764}\u{fe0f}\u{200d}\u{1f525}"), 2);
+        // Not RGI, so drawn as its parts.
+        assert_eq!(uts51.str_width("\u{1f4bb}\u{200d}\u{1f469}"), 4);
+    }
+
+    #[test]
+    fn ambiguous() {
+        let wide = AmbiguousWidth::Wide;
+        // WHITE CIRCLE is East Asian Ambiguous.
+        assert_eq!(UnicodeWidth::default().str_width("\u{25cb}"), 1);
+        assert_eq!(UnicodeWidth { ambiguous: wide }.str_width("\u{25cb}"), 2);
+        assert_eq!(Uts51 { ambiguous: wide }.str_width("a\u{25cb}"), 3);
+        assert_eq!(
+            HangulJamo { ambiguous: wide }.str_width("\u{ac00}\u{25cb}"),
+            4
+        );
+        assert_eq!(Bidi { ambiguous: wide }.str_width("\u{25cb}"), 2);
+    }
+
+    #[test]
+    fn hangul_jamo() {
+        // A syllable spelled with conjoining jamo, between some other text.
+        assert_eq!(
+            HangulJamo::default().str_width("a\u{1100}\u{1161}\u{11a8}b"),
+            4
+        );
+        let family = "\u{1f468}\u{200d}\u{1f469}\u{200d}\u{1f466}\u{200d}\u{1f466}";
+        assert_eq!(HangulJamo::default().str_width(family), family.width());
+        assert_eq!(
+            HangulJamo::default().str_width(&format!("\u{ac00}{}", family)),
+            2 + family.width()
+        );
+    }
+}
@@ -0,0 +1,221 @@
+//! Tables, with the columns sized by a [`WidthStrategy`], for `--table`. Like
+//! the boxes, if the strategy gets a cell's width wrong, the borders after it
+//! won't line up.
+use super::{draw::drawing, score::Strategy};
+use term_width_blog_example::{
+    ansi,
+    pad::{self, Align},
+    truncate, wrap, WidthStrategy,
+};
+
+/// Which characters to draw the borders with.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Border {
+    Ascii,
+    BoxDrawing,
+}
+
+impl Default for Border {
+    /// Whatever the boxes use, see [`drawing`].
+    fn default() -> Self {
+        if cfg!(feature = "box_drawing") {
+            Border::BoxDrawing
+        } else {
+            Border::Ascii
+        }
+    }
+}
+
+impl std::str::FromStr for Border {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "ascii" => Ok(Border::Ascii),
+            "box_drawing" | "box-drawing" => Ok(Border::BoxDrawing),
+            _ => Err(format!(
+                "unknown border {:?}, expected `ascii` or `box-drawing`",
+                s
+            )),
+        }
+    }
+}
+
+impl Border {
+    fn hline(self) -> &'static str {
+        match self {
+            Border::Ascii => drawing::ascii::HLINE,
+            Border::BoxDrawing => drawing::box_drawing::HLINE,
+        }
+    }
+    fn vline(self) -> &'static str {
+        match self {
+            Border::Ascii => drawing::ascii::VLINE,
+            Border::BoxDrawing => drawing::box_drawing::VLINE,
+        }
+    }
+    fn corners(self) -> &'static [char] {
+        match self {
+            Border::Ascii => drawing::ascii::CORNERS,
+            Border::BoxDrawing => drawing::box_drawing::CORNERS,
+        }
+    }
+    fn tees(self) -> &'static [char] {
+        match self {
+            Border::Ascii => drawing::ascii::TEES,
+            Border::BoxDrawing => drawing::box_drawing::TEES,
+        }
+    }
+    fn cross(self) -> char {
+        match self {
+            Border::Ascii => drawing::ascii::CROSS,
+            Border::BoxDrawing => drawing::box_drawing::CROSS,
+        }
+    }
+}
+
+/// What to do with a line too wide for its column.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Overflow {
+    /// Word wrap it onto more lines, see [`wrap::wrap`].
+    Wrap,
+    /// Cut the end off, see [`truncate::truncate_end`].
+    Truncate,
+}
+
+/// Settings for one column of a [`Table`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Column {
+    pub align: Align,
+    /// Columns the cells can use (not counting the space either side of
+    /// them). By default, as many as the widest cell needs.
+    pub max_width: Option<usize>,
+    pub overflow: Overflow,
+}
+
+impl Default for Column {
+    fn default() -> Self {
+        Self {
+            align: Align::Left,
+            max_width: None,
+            overflow: Overflow::Wrap,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Table {
+    pub border: Border,
+    /// Settings for each column. Columns past the end of this get
+    /// [`Column::default`].
+    pub columns: Vec<Column>,
+    /// Whether to draw a line under the first row.
+    pub header: bool,
+    /// Cells can have more than one line. Rows with fewer cells than the
+    /// others are filled out with empty ones.
+    pub rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    /// Lay out the table with `strat` measuring the cells, and return the lines
+    /// to print.
+    pub fn render(&self, strat: &dyn WidthStrategy) -> Vec<String> {
+        let ncols = self.rows.iter().map(|r| r.len()).max().unwrap_or_default();
+        let columns = (0..ncols)
+            .map(|i| self.columns.get(i).copied().unwrap_or_default())
+            .collect::<Vec<_>>();
+        let measure = |s: &str| strat.str_width(&ansi::strip(s));
+
+        let mut widths = vec![0; ncols];
+        for row in &self.rows {
+            for (i, cell) in row.iter().enumerate() {
+                let widest = cell.split('\n').map(measure).max().unwrap_or_default();
+                widths[i] = widths[i].max(widest);
+            }
+        }
+        for (width, col) in widths.iter_mut().zip(&columns) {
+            if let Some(max) = col.max_width {
+                *width = (*width).min(max);
+            }
+        }
+
+        let b = self.border;
+        let rule = |ends: (char, char), join: char| {
+            let segments = widths
+                .iter()
+                .map(|w| b.hline().repeat(w + 2))
+                .collect::<Vec<_>>();
+            format!("{}{}{}", ends.0, segments.join(&join.to_string()), ends.1)
+        };
+        let (corners, tees) = (b.corners(), b.tees());
+        let mut out = vec![rule((corners[0], corners[1]), tees[0])];
+        for (r, row) in self.rows.iter().enumerate() {
+            let cells = (0..ncols)
+                .map(|i| {
+                    let cell = row.get(i).map_or("", |c| c.as_str());
+                    cell_lines(cell, widths[i], &columns[i], strat)
+                })
+                .collect::<Vec<_>>();
+            let height = cells.iter().map(|c| c.len()).max().unwrap_or_default();
+            for l in 0..height.max(1) {
+                let mut line = b.vline().to_string();
+                for (i, cell) in cells.iter().enumerate() {
+                    let text = cell.get(l).map_or("", |s| s.as_str());
+                    let text = pad::pad(text, widths[i], columns[i].align, strat);
+                    line.push_str(&format!(" {} {}", text, b.vline()));
+                }
+                out.push(line);
+            }
+            if self.header && r == 0 && self.rows.len() > 1 {
+                out.push(rule((tees[2], tees[3]), b.cross()));
+            }
+        }
+        out.push(rule((corners[2], corners[3]), tees[1]));
+        out
+    }
+}
+
+/// The lines of `cell`, fit into `width` columns as `col` says.
+fn cell_lines(cell: &str, width: usize, col: &Column, strat: &dyn WidthStrategy) -> Vec<String> {
+    cell.split('\n')
+        .flat_map(|line| match col.overflow {
+            Overflow::Wrap if strat.str_width(&ansi::strip(line)) > width => {
+                wrap::wrap(line, width, strat)
+            }
+            Overflow::Wrap => vec![line.to_string()],
+            Overflow::Truncate => vec![truncate::truncate_end(line, width, strat).into_owned()],
+        })
+        .collect()
+}
+
+/// The table for `--table`: a row for each phrase, with how wide each of
+/// `strategies` thinks it (or its widest line) is.
+pub fn phrase_matrix(phrases: &[String], strategies: &[&Strategy], phrase_column: Column) -> Table {
+    let mut header = vec!["phrase".to_string()];
+    header.extend(strategies.iter().map(|s| s.0.to_string()));
+    let mut rows = vec![header];
+    for phrase in phrases {
+        let mut row = vec![phrase.clone()];
+        row.extend(strategies.iter().map(|s| {
+            let widest = phrase
+                .split('\n')
+                .map(|l| s.1.str_width(&ansi::strip(l)))
+                .max();
+            widest.unwrap_or_default().to_string()
+        }));
+        rows.push(row);
+    }
+    // Narrow columns for the numbers, so the strategy names wrap.
+    let number = Column {
+        align: Align::Right,
+        max_width: Some(8),
+        overflow: Overflow::Wrap,
+    };
+    let mut columns = vec![phrase_column];
+    columns.resize(1 + strategies.len(), number);
+    Table {
+        columns,
+        header: true,
+        rows,
+        ..Table::default()
+    }
+}
@@ -1,17 +1,50 @@
-// Small terminal library. unix-only, and hardcodes escapes rather than using
-// terminfo, although I believe any of this could be implemented to do the right
-// thing fairly easily.
+// Small terminal library. unix-only. Escapes come from terminfo where it has
+// them, falling back to the usual xterm ones.
 use super::Result;
 use libc::termios;
 use std::fs::File;
-use std::io::Write;
+use std::io::{Read, Write};
 use std::os::unix::io::AsRawFd;
+use std::time::{Duration, Instant};
+
+mod info;
+mod input;
+mod terminfo;
+pub use info::TerminalInfo;
+use input::Csi;
+pub use input::ReplyError;
+use terminfo::{cap, Terminfo};
+
+/// DEC private mode 2027, where the terminal measures text a grapheme cluster
+/// at a time rather than a codepoint at a time.
+pub const GRAPHEME_CLUSTERING: u16 = 2027;
+
+/// How long to wait for the terminal to reply to a query, unless changed with
+/// [`Terminal::set_timeout`].
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// How long a DCS reply can take to finish once it's started, before we
+/// decide the `ESC P` was a keystroke.
+const DCS_TIMEOUT: Duration = Duration::from_millis(100);
 
 pub struct Terminal {
     size: (u16, u16),
     prev: Option<termios>,
     tty: File,
     no_color: bool,
+    timeout: Duration,
+    /// Read, but not parsed yet.
+    pending: Vec<u8>,
+    /// Input that arrived while waiting for a reply, but wasn't it.
+    input: Vec<u8>,
+    info: Option<TerminalInfo>,
+    /// For `$TERM`, if there's an entry for it.
+    terminfo: Option<Terminfo>,
+    /// Set by [`Terminal::set_scroll_region`].
+    scroll_region: Option<(u16, u16)>,
+    /// DEC private modes we've changed: what they were to start with, and
+    /// what they are now.
+    modes: Vec<(u16, ModeState, ModeState)>,
 }
 
 impl Terminal {
@@ -41,72 +74,320 @@ impl Terminal {
             tty,
             size,
             no_color,
+            timeout: DEFAULT_TIMEOUT,
+            pending: vec![],
+            input: vec![],
+            info: None,
+            terminfo: Terminfo::from_env().ok(),
+            scroll_region: None,
+            modes: vec![],
         })
     }
 
+    /// How long to wait for each reply to a query before giving up with
+    /// [`ReplyError::Timeout`].
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Take everything read while waiting for replies that wasn't one, like
+    /// keystrokes, in the order it arrived.
+    pub fn take_input(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.input)
+    }
+
     pub fn size(&self) -> (u16, u16) {
         self.size
     }
 
     pub fn write(&mut self, s: &str) -> Result<()> {
-        self.tty.write(s.as_bytes())?;
+        self.tty.write_all(s.as_bytes())?;
         Ok(())
     }
-    // write `\E[6n`, get back `\E[{y};{x}R`. We do this in a dumb way but a
-    // serious application would have this code written.
-    pub fn get_pos(&mut self) -> Result<(u16, u16)> {
-        use std::io::Read;
+    // write `\E[6n`, get back `\E[{y};{x}R`.
+    pub fn get_pos(&mut self) -> Result<(u16, u16), ReplyError> {
+        self.tty.flush()?;
+        self.request_pos()?;
         self.tty.flush()?;
-        self.tty.write_all(b"\x1b[6n")?;
+        self.read_pos_reply()
+    }
+
+    /// Just the writing half of `get_pos`. Lets you send several queries
+    /// before waiting on any replies, which matters when there are a lot of
+    /// them.
+    pub fn request_pos(&mut self) -> std::io::Result<()> {
+        self.tty.write_all(b"\x1b[6n")
+    }
+
+    /// Just the reading half of `get_pos`.
+    pub fn read_pos_reply(&mut self) -> Result<(u16, u16), ReplyError> {
+        // Note that some terminals send the same thing for shift+F3 (or
+        // other modifiers), so typing that now would confuse us.
+        let csi = match self.read_reply(|e| {
+            matches!(e, input::Event::Csi(c)
+                if c.final_byte == b'R' && c.private().is_none() && c.intermediates.is_empty())
+        })? {
+            input::Event::Csi(csi) => csi,
+            _ => unreachable!(),
+        };
+        match csi.numbers()?[..] {
+            [row, col] => Ok((col.unwrap_or(1), row.unwrap_or(1))),
+            _ => Err(ReplyError::Malformed(csi.to_string())),
+        }
+    }
+
+    /// Ask the terminal what it is, see [`TerminalInfo`]. Only the first call
+    /// sends any queries, later ones return the same answer.
+    pub fn info(&mut self) -> Result<TerminalInfo, ReplyError> {
+        if let Some(info) = &self.info {
+            return Ok(info.clone());
+        }
+        // XTVERSION, DA2, then DA1. Everything answers DA1, and answers in
+        // order, so once its reply arrives there's no use waiting on the
+        // others.
+        self.tty.write_all(b"\x1b[>0q\x1b[>c\x1b[c")?;
+        self.tty.flush()?;
+        let mut info = TerminalInfo::default();
+        loop {
+            let reply = self.read_reply(|e| match e {
+                input::Event::Csi(c) => {
+                    c.final_byte == b'c' && matches!(c.private(), Some(b'?' | b'>'))
+                }
+                input::Event::Dcs(d) => d.starts_with(b">|"),
+                input::Event::Byte(_) => false,
+            })?;
+            match reply {
+                input::Event::Csi(c) if c.private() == Some(b'>') => info.set_da2(&c)?,
+                input::Event::Csi(c) => {
+                    info.set_da1(&c)?;
+                    break;
+                }
+                input::Event::Dcs(d) => info.set_xtversion(&d[2..]),
+                input::Event::Byte(_) => unreachable!(),
+            }
+        }
+        self.info = Some(info.clone());
+        Ok(info)
+    }
+
+    /// Ask about a DEC private mode with DECRQM.
+    pub fn query_mode(&mut self, mode: u16) -> Result<ModeState, ReplyError> {
+        if let Some(&(_, _, now)) = self.modes.iter().find(|m| m.0 == mode) {
+            return Ok(now);
+        }
+        // Followed by DA1, since terminals that don't know DECRQM won't
+        // answer it.
+        write!(self.tty, "\x1b[?{}$p\x1b[c", mode)?;
         self.tty.flush()?;
-        let mut v = String::new();
-        let mut saw_esc = false;
+        let is_da1 = |c: &Csi| c.final_byte == b'c' && c.private() == Some(b'?');
+        let is_decrpm =
+            |c: &Csi| c.final_byte == b'y' && c.private() == Some(b'?') && c.intermediates == b"$";
+        let csi = match self
+            .read_reply(|e| matches!(e, input::Event::Csi(c) if is_da1(c) || is_decrpm(c)))?
+        {
+            input::Event::Csi(c) => c,
+            _ => unreachable!(),
+        };
+        if is_da1(&csi) {
+            return Ok(ModeState::NotRecognized);
+        }
+        // Swallow the DA1 reply that's still coming.
+        self.read_reply(|e| matches!(e, input::Event::Csi(c) if is_da1(c)))?;
+        match csi.numbers()?[..] {
+            [Some(m), state] if m == mode => Ok(ModeState::from(state.unwrap_or(0))),
+            _ => Err(ReplyError::Malformed(csi.to_string())),
+        }
+    }
+
+    /// Turn a DEC private mode on or off, if the terminal has it and lets it
+    /// be changed. Returns what it was before. Whatever it was when first
+    /// changed gets put back when the `Terminal` is dropped.
+    pub fn set_mode(&mut self, mode: u16, on: bool) -> Result<ModeState, ReplyError> {
+        let prev = self.query_mode(mode)?;
+        let next = match prev {
+            ModeState::Set | ModeState::Reset if on => ModeState::Set,
+            ModeState::Set | ModeState::Reset => ModeState::Reset,
+            // Nothing to do.
+            _ => return Ok(prev),
+        };
+        write!(self.tty, "\x1b[?{}{}", mode, if on { 'h' } else { 'l' })?;
+        match self.modes.iter_mut().find(|m| m.0 == mode) {
+            Some(m) => m.2 = next,
+            None => self.modes.push((mode, prev, next)),
+        }
+        Ok(prev)
+    }
+
+    /// Wait for something `is_reply` accepts, setting aside anything else
+    /// that arrives first for [`Terminal::take_input`].
+    fn read_reply(
+        &mut self,
+        is_reply: impl Fn(&input::Event) -> bool,
+    ) -> Result<input::Event, ReplyError> {
+        let deadline = Instant::now() + self.timeout;
+        // When the unfinished `ESC P` at the start of `pending` showed up.
+        let mut dcs_since = None;
         loop {
-            // this is a dumb way to read this but we don't want to read past
-            // what we need to, which could block.
-            let mut buf = [0u8; 1];
-            let size = self.tty.read(&mut buf)?;
-            if size == 0 {
+            while let Some((event, len)) = input::parse(&self.pending) {
+                if is_reply(&event) {
+                    self.pending.drain(..len);
+                    return Ok(event);
+                }
+                self.input.extend(self.pending.drain(..len));
+            }
+            // Checked here too, since `fill` only notices if nothing arrives.
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(ReplyError::Timeout);
+            }
+            if !self.pending.starts_with(b"\x1bP") {
+                dcs_since = None;
+                self.fill(deadline)?;
                 continue;
             }
-            if !saw_esc {
-                saw_esc = buf[0] == 0x1b;
-            } else {
-                if buf[0] == b'R' {
-                    break;
+            // Alt+P sends `ESC P` too, and the terminal won't end that. If
+            // it's taking too long, it was probably typed.
+            let dcs_deadline = (*dcs_since.get_or_insert(now) + DCS_TIMEOUT).min(deadline);
+            match self.fill(dcs_deadline) {
+                Err(ReplyError::Timeout) if dcs_deadline < deadline => {
+                    self.input.push(self.pending.remove(0));
+                    dcs_since = None;
                 }
-                v.push(buf[0] as char);
+                r => r?,
             }
         }
-        let mut fields = v.split(';');
-        // v should be `[row;col`
-        let row = fields.next().unwrap()[1..].parse::<u16>()?;
-        let col = fields.next().unwrap().parse::<u16>()?;
-        Ok((col, row))
     }
 
-    pub fn move_to(&mut self, x: u16, y: u16) -> Result<()> {
-        // real code should look up `cup` in terminfo
-        self.tty
-            .write_all(format!("\x1b[{};{}H", y.max(1), x.max(1)).as_bytes())?;
+    /// Wait until there's something to read (or `deadline`), and read it into
+    /// `pending`. Only what's already there gets read, so this won't block.
+    fn fill(&mut self, deadline: Instant) -> Result<(), ReplyError> {
+        let left = deadline.saturating_duration_since(Instant::now());
+        let mut fd = libc::pollfd {
+            fd: self.tty.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        // Round up, so we don't spin on a timeout of 0ms before the deadline.
+        let ms = left.as_micros().div_ceil(1000).min(i32::MAX as u128) as libc::c_int;
+        match unsafe { libc::poll(&mut fd, 1, ms) } {
+            -1 => {
+                let e = std::io::Error::last_os_error();
+                if e.kind() == std::io::ErrorKind::Interrupted {
+                    return Ok(());
+                }
+                return Err(e.into());
+            }
+            0 => return Err(ReplyError::Timeout),
+            _ => {}
+        }
+        let mut buf = [0u8; 256];
+        match self.tty.read(&mut buf)? {
+            0 => Err(ReplyError::Closed),
+            n => {
+                self.pending.extend_from_slice(&buf[..n]);
+                Ok(())
+            }
+        }
+    }
+
+    /// Find out how many columns the terminal really uses for `s`, by writing
+    /// it at the start of row `y` and asking where the cursor ended up. Leaves
+    /// everything from row `y` down cleared.
+    ///
+    /// Note that if `s` exactly fills a row, most terminals leave the cursor on
+    /// the last column rather than wrapping, so this will be off by one.
+    pub fn measure(&mut self, y: u16, s: &str) -> Result<usize> {
+        self.move_to(1, y)?;
+        self.clear(Clear::ToEndOfScreen)?;
+        self.write(s)?;
+        let (ex, ey) = self.get_pos()?;
+        let rows = ey.saturating_sub(y) as usize;
+        let width = rows * self.size.0 as usize + ex.saturating_sub(1) as usize;
+        self.move_to(1, y)?;
+        self.clear(Clear::ToEndOfScreen)?;
+        Ok(width)
+    }
+
+    /// Write the terminfo capability `cap` with `params` filled in, or
+    /// `fallback` if there's no entry or it doesn't have that capability.
+    fn write_cap(&mut self, cap: usize, params: &[i32], fallback: &str) -> Result<()> {
+        match self.terminfo.as_ref().and_then(|ti| ti.expand(cap, params)) {
+            Some(seq) => self.tty.write_all(&seq)?,
+            None => self.tty.write_all(fallback.as_bytes())?,
+        }
         Ok(())
     }
+
+    pub fn move_to(&mut self, x: u16, y: u16) -> Result<()> {
+        let (x, y) = (x.max(1), y.max(1));
+        // `cup` counts from 0.
+        let fallback = format!("\x1b[{};{}H", y, x);
+        self.write_cap(cap::CUP, &[y as i32 - 1, x as i32 - 1], &fallback)
+    }
     pub fn clear(&mut self, clear: Clear) -> Result<()> {
-        self.tty.write_all(match clear {
-            Clear::ToEndOfScreen => b"\x1b[0J",
-            Clear::ToStartOfScreen => b"\x1b[1J",
-            Clear::FullScreen => b"\x1b[2J",
-            Clear::ToStartOfLine => b"\x1b[0K",
-            Clear::ToEndOfLine => b"\x1b[1K",
-            Clear::FullLine => b"\x1b[2K",
-        })?;
-        Ok(())
+        match clear {
+            Clear::ToEndOfScreen => self.write_cap(cap::ED, &[], "\x1b[0J"),
+            Clear::ToStartOfScreen => self.write("\x1b[1J"),
+            Clear::FullScreen => self.write("\x1b[2J"),
+            Clear::ToStartOfLine => self.write_cap(cap::EL1, &[], "\x1b[1K"),
+            Clear::ToEndOfLine => self.write_cap(cap::EL, &[], "\x1b[0K"),
+            Clear::FullLine => self.write("\x1b[2K"),
+        }
     }
+    /// Scroll the contents up by `n` rows, adding blank ones at the bottom.
     pub fn scroll(&mut self, n: u16) -> Result<()> {
-        write!(self.tty, "\x1b[{}S", n)?;
+        self.write_cap(cap::INDN, &[n as i32], &format!("\x1b[{}S", n))?;
         self.tty.flush()?;
         Ok(())
     }
+    /// Scroll the contents down by `n` rows, adding blank ones at the top.
+    pub fn scroll_reverse(&mut self, n: u16) -> Result<()> {
+        self.write_cap(cap::RIN, &[n as i32], &format!("\x1b[{}T", n))?;
+        self.tty.flush()?;
+        Ok(())
+    }
+
+    /// Only scroll rows `top` to `bottom` (inclusive, counting from 1), with
+    /// DECSTBM. This applies to scrolling with [`Terminal::scroll`], and to
+    /// inserting and deleting lines, as well as to text running off the
+    /// bottom. Most terminals move the cursor to the top left when this is
+    /// set. Dropping the `Terminal` puts back the whole screen.
+    pub fn set_scroll_region(&mut self, top: u16, bottom: u16) -> Result<()> {
+        let (top, bottom) = (top.max(1), bottom.max(1));
+        let fallback = format!("\x1b[{};{}r", top, bottom);
+        self.write_cap(cap::CSR, &[top as i32 - 1, bottom as i32 - 1], &fallback)?;
+        self.scroll_region = Some((top, bottom));
+        Ok(())
+    }
+    /// Scroll the whole screen again, and leave the cursor where it was.
+    pub fn reset_scroll_region(&mut self) -> Result<()> {
+        if self.scroll_region.take().is_none() {
+            return Ok(());
+        }
+        let rows = self.size.1;
+        self.write_cap(cap::SC, &[], "\x1b7")?;
+        self.write_cap(cap::CSR, &[0, rows as i32 - 1], "\x1b[r")?;
+        self.write_cap(cap::RC, &[], "\x1b8")
+    }
+
+    /// Insert `n` blank lines at the cursor, pushing the ones below it down
+    /// (and off the bottom of the scroll region).
+    pub fn insert_lines(&mut self, n: u16) -> Result<()> {
+        self.write_cap(cap::IL, &[n as i32], &format!("\x1b[{}L", n))
+    }
+    /// Delete `n` lines from the cursor down, pulling the ones below up.
+    pub fn delete_lines(&mut self, n: u16) -> Result<()> {
+        self.write_cap(cap::DL, &[n as i32], &format!("\x1b[{}M", n))
+    }
+    /// Insert `n` blank cells at the cursor, pushing the rest of the line
+    /// right.
+    pub fn insert_chars(&mut self, n: u16) -> Result<()> {
+        self.write_cap(cap::ICH, &[n as i32], &format!("\x1b[{}@", n))
+    }
+    /// Delete `n` cells from the cursor on, pulling the rest of the line left.
+    pub fn delete_chars(&mut self, n: u16) -> Result<()> {
+        self.write_cap(cap::DCH, &[n as i32], &format!("\x1b[{}P", n))
+    }
     /// separate than normal write, since on windows this would be:
     /// - doing a syscall to change console color
     /// - doing normal print
@@ -115,7 +396,13 @@ impl Terminal {
         if self.no_color {
             self.tty.write_all(s.as_bytes())?;
         } else {
-            write!(self.tty, "\x1b[3{}m{}\x1b[m", color as u8, s)?;
+            self.write_cap(
+                cap::SETAF,
+                &[color as i32],
+                &format!("\x1b[3{}m", color as u8),
+            )?;
+            self.tty.write_all(s.as_bytes())?;
+            self.write_cap(cap::SGR0, &[], "\x1b[m")?;
         }
         Ok(())
     }
@@ -167,6 +454,18 @@ impl Write for Terminal {
 
 impl Drop for Terminal {
     fn drop(&mut self) {
+        let _ = self.reset_scroll_region();
+        for &(mode, start, now) in &self.modes {
+            if start != now {
+                let _ = write!(
+                    self.tty,
+                    "\x1b[?{}{}",
+                    mode,
+                    if start.is_set() { 'h' } else { 'l' }
+                );
+            }
+        }
+        let _ = self.tty.flush();
         if let Some(prev) = self.prev {
             unsafe {
                 if libc::tcsetattr(self.tty.as_raw_fd(), 0, &prev) == -1 {
@@ -189,6 +488,34 @@ fn get_termios(fd: libc::c_int) -> Result<termios> {
     }
 }
 
+/// A DEC private mode's state, as DECRQM reports it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ModeState {
+    NotRecognized,
+    Set,
+    Reset,
+    PermanentlySet,
+    PermanentlyReset,
+}
+
+impl ModeState {
+    pub fn is_set(self) -> bool {
+        matches!(self, ModeState::Set | ModeState::PermanentlySet)
+    }
+}
+
+impl From<u16> for ModeState {
+    fn from(n: u16) -> Self {
+        match n {
+            1 => ModeState::Set,
+            2 => ModeState::Reset,
+            3 => ModeState::PermanentlySet,
+            4 => ModeState::PermanentlyReset,
+            _ => ModeState::NotRecognized,
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum Clear {
     FullScreen,
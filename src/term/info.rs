@@ -0,0 +1,86 @@
+//! What the terminal says it is, from its answers to XTVERSION and the
+//! Primary and Secondary Device Attributes (DA1 and DA2) queries. `$TERM`
+//! can't be trusted for this: half of everything claims to be
+//! `xterm-256color`.
+use super::input::{Csi, ReplyError};
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TerminalInfo {
+    /// Like `xterm` or `WezTerm`, from XTVERSION if it's supported, or else
+    /// guessed from DA2 for the few terminals that have their own ID there.
+    pub name: Option<String>,
+    /// From XTVERSION if it's supported, or else the firmware version in DA2.
+    pub version: Option<String>,
+    /// The DA2 terminal type ID, e.g. 1 for a VT220 or 41 for a VT420.
+    pub id: Option<u16>,
+    /// The DA1 attributes, after the first (which says what level of VT it
+    /// is). E.g. 4 means sixel graphics, and 22 ANSI color.
+    pub features: Vec<u16>,
+}
+
+impl TerminalInfo {
+    /// `ESC [ > id ; version ; cartridge c`
+    pub(super) fn set_da2(&mut self, csi: &Csi) -> Result<(), ReplyError> {
+        let (id, version) = match csi.numbers()?[..] {
+            [id, version, ..] => (id.unwrap_or(0), version.unwrap_or(0)),
+            _ => return Err(ReplyError::Malformed(csi.to_string())),
+        };
+        self.id = Some(id);
+        if self.name.is_none() {
+            self.name = match id {
+                77 => Some("mintty"),
+                82 => Some("rxvt"),
+                83 => Some("screen"),
+                84 => Some("tmux"),
+                85 => Some("rxvt-unicode"),
+                _ => None,
+            }
+            .map(String::from);
+        }
+        self.version.get_or_insert_with(|| version.to_string());
+        Ok(())
+    }
+
+    /// `ESC [ ? level ; features... c`
+    pub(super) fn set_da1(&mut self, csi: &Csi) -> Result<(), ReplyError> {
+        self.features = csi.numbers()?.into_iter().skip(1).flatten().collect();
+        Ok(())
+    }
+
+    /// What's between `ESC P > |` and `ESC \`, usually either `name(version)`
+    /// or `name version`.
+    pub(super) fn set_xtversion(&mut self, reply: &[u8]) {
+        let reply = String::from_utf8_lossy(reply);
+        let reply = reply.trim();
+        let (name, version) = match reply.split_once(['(', ' ']) {
+            Some((name, version)) => (name, Some(version.trim_end_matches(')'))),
+            None => (reply, None),
+        };
+        self.name = Some(name.to_string());
+        // This is more specific than what DA2 gave, if it came first.
+        self.version = version.map(String::from).or(self.version.take());
+    }
+
+    /// The name of the test that's the best guess at how this terminal
+    /// measures text, going by what it's known to do.
+    pub fn default_strategy(&self) -> &'static str {
+        match self.name.as_deref().map(str::to_ascii_lowercase).as_deref() {
+            // Its own tables, which is what `termwiz_ish` copies.
+            Some("wezterm") => "termwiz_ish",
+            // These cluster graphemes and follow UTS #51 for emoji.
+            Some("kitty" | "foot" | "contour" | "ghostty") => "uts51",
+            Some("xterm" | "tmux" | "screen" | "mintty") => "widecharwidth_rec",
+            _ => "unicode_width",
+        }
+    }
+}
+
+impl std::fmt::Display for TerminalInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name.as_deref().unwrap_or("unknown"))?;
+        if let Some(version) = &self.version {
+            write!(f, " {}", version)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,201 @@
+//! Picking the terminal's replies to queries out of whatever else shows up on
+//! its input, like keystrokes typed while we wait.
+use std::fmt;
+
+const ESC: u8 = 0x1b;
+
+/// A CSI sequence, `ESC [ params intermediates final`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Csi {
+    /// Everything from `0x30` to `0x3f`, including a leading private marker
+    /// like `?` or `>`.
+    pub params: Vec<u8>,
+    pub intermediates: Vec<u8>,
+    pub final_byte: u8,
+}
+
+impl Csi {
+    /// The private marker (`<`, `=`, `>` or `?`) the parameters start with.
+    pub fn private(&self) -> Option<u8> {
+        self.params.first().copied().filter(|b| b"<=>?".contains(b))
+    }
+
+    /// The `;` separated numeric parameters after any private marker. Empty
+    /// ones are `None`.
+    pub fn numbers(&self) -> Result<Vec<Option<u16>>, ReplyError> {
+        let start = self.private().map_or(0, |_| 1);
+        std::str::from_utf8(&self.params[start..])
+            .ok()
+            .and_then(|s| {
+                s.split(';')
+                    .map(|n| match n {
+                        "" => Some(None),
+                        _ => n.parse().ok().map(Some),
+                    })
+                    .collect()
+            })
+            .ok_or_else(|| ReplyError::Malformed(self.to_string()))
+    }
+}
+
+impl fmt::Display for Csi {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut bytes = b"\\e[".to_vec();
+        bytes.extend(&self.params);
+        bytes.extend(&self.intermediates);
+        bytes.push(self.final_byte);
+        write!(f, "{}", String::from_utf8_lossy(&bytes))
+    }
+}
+
+/// One thing read from the terminal.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Event {
+    Csi(Csi),
+    /// The contents of a DCS string, between `ESC P` and `ESC \`.
+    Dcs(Vec<u8>),
+    /// Any other byte: typing, or part of a sequence we don't understand.
+    Byte(u8),
+}
+
+/// Parse the event at the start of `buf`, and return it with how many bytes
+/// it took up. `None` means `buf` ends partway through a sequence, and more
+/// input is needed to tell.
+pub fn parse(buf: &[u8]) -> Option<(Event, usize)> {
+    let first = *buf.first()?;
+    if first != ESC {
+        return Some((Event::Byte(first), 1));
+    }
+    match buf.get(1) {
+        None => return None,
+        Some(b'[') => {}
+        Some(b'P') => {
+            // An `ESC` that isn't the start of `ESC \` means this wasn't a
+            // DCS after all, but Alt+P, with something (like the reply we're
+            // waiting for) after it.
+            let esc = 2 + buf[2..].iter().position(|&b| b == ESC)?;
+            return match buf.get(esc + 1)? {
+                b'\\' => Some((Event::Dcs(buf[2..esc].to_vec()), esc + 2)),
+                _ => Some((Event::Byte(ESC), 1)),
+            };
+        }
+        Some(_) => return Some((Event::Byte(ESC), 1)),
+    }
+    let mut i = 2;
+    let params_end = loop {
+        match *buf.get(i)? {
+            0x30..=0x3f => i += 1,
+            _ => break i,
+        }
+    };
+    let intermediates_end = loop {
+        match *buf.get(i)? {
+            0x20..=0x2f => i += 1,
+            _ => break i,
+        }
+    };
+    match buf[i] {
+        final_byte @ 0x40..=0x7e => Some((
+            Event::Csi(Csi {
+                params: buf[2..params_end].to_vec(),
+                intermediates: buf[params_end..intermediates_end].to_vec(),
+                final_byte,
+            }),
+            i + 1,
+        )),
+        // Not a CSI sequence after all, so just pass the `ESC` along.
+        _ => Some((Event::Byte(ESC), 1)),
+    }
+}
+
+/// Why a reply to a query couldn't be read.
+#[derive(Debug)]
+pub enum ReplyError {
+    /// Nothing came back in time. The terminal probably doesn't support the
+    /// query.
+    Timeout,
+    /// The terminal's input was closed.
+    Closed,
+    /// It looked like the reply, but didn't make sense.
+    Malformed(String),
+    Io(std::io::Error),
+}
+
+impl fmt::Display for ReplyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplyError::Timeout => f.write_str("timed out waiting for the terminal to reply"),
+            ReplyError::Closed => f.write_str("terminal closed before replying"),
+            ReplyError::Malformed(s) => write!(f, "malformed reply from the terminal: {}", s),
+            ReplyError::Io(e) => write!(f, "couldn't read the terminal's reply: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ReplyError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ReplyError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for ReplyError {
+    fn from(e: std::io::Error) -> Self {
+        ReplyError::Io(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn csi(params: &[u8], intermediates: &[u8], final_byte: u8) -> Event {
+        Event::Csi(Csi {
+            params: params.to_vec(),
+            intermediates: intermediates.to_vec(),
+            final_byte,
+        })
+    }
+
+    #[test]
+    fn bytes() {
+        assert_eq!(parse(b""), None);
+        assert_eq!(parse(b"ab"), Some((Event::Byte(b'a'), 1)));
+    }
+
+    #[test]
+    fn csi_sequences() {
+        assert_eq!(parse(b"\x1b[?62;4cx"), Some((csi(b"?62;4", b"", b'c'), 8)));
+        assert_eq!(parse(b"\x1b[2 q"), Some((csi(b"2", b" ", b'q'), 5)));
+        // Not a final byte, so not a CSI sequence.
+        assert_eq!(parse(b"\x1b[1\x07"), Some((Event::Byte(ESC), 1)));
+    }
+
+    #[test]
+    fn partial() {
+        assert_eq!(parse(b"\x1b"), None);
+        assert_eq!(parse(b"\x1b["), None);
+        assert_eq!(parse(b"\x1b[12;3"), None);
+        assert_eq!(parse(b"\x1b[2 "), None);
+        assert_eq!(parse(b"\x1bP>|tmux"), None);
+        assert_eq!(parse(b"\x1bP>|tmux\x1b"), None);
+    }
+
+    #[test]
+    fn other_escapes() {
+        // Alt+x.
+        assert_eq!(parse(b"\x1bx"), Some((Event::Byte(ESC), 1)));
+    }
+
+    #[test]
+    fn dcs_or_alt_p() {
+        assert_eq!(
+            parse(b"\x1bP>|tmux 3.3a\x1b\\x"),
+            Some((Event::Dcs(b">|tmux 3.3a".to_vec()), 15))
+        );
+        // Alt+P, then a reply.
+        assert_eq!(parse(b"\x1bP\x1b[?62c"), Some((Event::Byte(ESC), 1)));
+    }
+}
@@ -0,0 +1,465 @@
+//! Reading compiled terminfo entries, and filling in their parameters, so the
+//! escape sequences come from the terminal's description rather than being
+//! assumed. Only the standard capabilities are read, not the extended ones
+//! (like `Smulx`) after them.
+//!
+//! See `term(5)` for the file format, and `terminfo(5)` for the parameter
+//! language.
+use crate::Result;
+use std::convert::TryFrom;
+use std::fmt;
+use std::path::PathBuf;
+
+/// Indices of the string capabilities we use, in the order `term.h` has them.
+pub mod cap {
+    pub const CSR: usize = 3;
+    pub const EL: usize = 6;
+    pub const ED: usize = 7;
+    pub const CUP: usize = 10;
+    pub const SGR0: usize = 39;
+    pub const DCH: usize = 105;
+    pub const DL: usize = 106;
+    pub const ICH: usize = 108;
+    pub const INDN: usize = 109;
+    pub const IL: usize = 110;
+    pub const RIN: usize = 113;
+    pub const RC: usize = 126;
+    pub const SC: usize = 128;
+    pub const EL1: usize = 269;
+    pub const SETAF: usize = 359;
+}
+
+/// `magic` for the original format, with 16-bit numbers.
+const MAGIC: i16 = 0o432;
+/// `magic` for the extended number format ncurses 6.1 added, with 32-bit
+/// numbers.
+const MAGIC_32BIT: i16 = 0o1036;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseTerminfoError(pub String);
+
+impl fmt::Display for ParseTerminfoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "bad terminfo entry: {}", self.0)
+    }
+}
+
+impl std::error::Error for ParseTerminfoError {}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Terminfo {
+    /// The terminal's names, separated by `|`. The last is a description.
+    pub names: String,
+    bools: Vec<bool>,
+    /// Absent or cancelled ones are `None`.
+    numbers: Vec<Option<i32>>,
+    strings: Vec<Option<Vec<u8>>>,
+}
+
+impl Terminfo {
+    /// Find and read the entry for `$TERM`.
+    pub fn from_env() -> Result<Self> {
+        let name = std::env::var("TERM").map_err(|_| "TERM isn't set")?;
+        Self::load(&name)
+    }
+
+    /// Find and read the entry for the terminal called `name`, looking in
+    /// the same places ncurses does.
+    pub fn load(name: &str) -> Result<Self> {
+        let first = match name.chars().next() {
+            Some(c) if !name.contains('/') => c,
+            _ => return Err(format!("bad terminal name {:?}", name).into()),
+        };
+        for dir in search_dirs() {
+            // Usually by first letter, but by its hex code on filesystems
+            // that ignore case.
+            for sub in [first.to_string(), format!("{:02x}", first as u32)] {
+                let path = dir.join(sub).join(name);
+                if let Ok(data) = std::fs::read(&path) {
+                    return Ok(Self::parse(&data)?);
+                }
+            }
+        }
+        Err(format!("no terminfo entry for {:?}", name).into())
+    }
+
+    pub fn parse(data: &[u8]) -> Result<Self, ParseTerminfoError> {
+        let err = |msg: &str| ParseTerminfoError(msg.to_string());
+        let short = |i: usize| {
+            data.get(i..i + 2)
+                .map(|b| i16::from_le_bytes([b[0], b[1]]))
+                .ok_or_else(|| err("truncated"))
+        };
+        let number_size = match short(0)? {
+            MAGIC => 2,
+            MAGIC_32BIT => 4,
+            _ => return Err(err("unknown magic number")),
+        };
+        let count = |i: usize| usize::try_from(short(i)?).map_err(|_| err("negative section size"));
+        let (names_len, bools_len) = (count(2)?, count(4)?);
+        let (numbers_len, strings_len, table_len) = (count(6)?, count(8)?, count(10)?);
+
+        let mut pos = 12;
+        let mut take = |len: usize| {
+            let s = data.get(pos..pos + len).ok_or_else(|| err("truncated"))?;
+            pos += len;
+            Ok::<_, ParseTerminfoError>(s)
+        };
+        let names = take(names_len)?;
+        let names = String::from_utf8_lossy(names.split(|&b| b == 0).next().unwrap_or_default());
+        let bools = take(bools_len)?.iter().map(|&b| b == 1).collect();
+        // Numbers start on an even byte.
+        if (names_len + bools_len) % 2 == 1 {
+            take(1)?;
+        }
+        let numbers = take(numbers_len * number_size)?
+            .chunks(number_size)
+            .map(|b| match *b {
+                [a, b] => i16::from_le_bytes([a, b]) as i32,
+                [a, b, c, d] => i32::from_le_bytes([a, b, c, d]),
+                _ => unreachable!(),
+            })
+            .map(|n| Some(n).filter(|&n| n >= 0))
+            .collect();
+        let offsets = take(strings_len * 2)?.to_vec();
+        let table = take(table_len)?;
+        let strings = offsets
+            .chunks(2)
+            .map(|b| match i16::from_le_bytes([b[0], b[1]]) {
+                // Absent or cancelled.
+                n if n < 0 => Ok(None),
+                n => {
+                    let s = table
+                        .get(n as usize..)
+                        .ok_or_else(|| err("bad string offset"))?;
+                    let end = s.iter().position(|&b| b == 0).unwrap_or(s.len());
+                    Ok(Some(s[..end].to_vec()))
+                }
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            names: names.into_owned(),
+            bools,
+            numbers,
+            strings,
+        })
+    }
+
+    /// A boolean capability, by its index in `term.h`.
+    pub fn flag(&self, i: usize) -> bool {
+        self.bools.get(i).copied().unwrap_or(false)
+    }
+
+    /// A numeric capability, by its index in `term.h`.
+    pub fn number(&self, i: usize) -> Option<i32> {
+        self.numbers.get(i).copied().flatten()
+    }
+
+    /// A string capability, by its index in `term.h` (see [`cap`]), as is.
+    pub fn string(&self, i: usize) -> Option<&[u8]> {
+        self.strings.get(i)?.as_deref()
+    }
+
+    /// A string capability with `params` filled in, see [`expand`].
+    pub fn expand(&self, i: usize, params: &[i32]) -> Option<Vec<u8>> {
+        self.string(i).map(|s| expand(s, params))
+    }
+}
+
+/// Where to look for terminfo entries, in order.
+fn search_dirs() -> Vec<PathBuf> {
+    let mut dirs = vec![];
+    if let Some(dir) = std::env::var_os("TERMINFO") {
+        dirs.push(dir.into());
+    }
+    if let Some(home) = std::env::var_os("HOME") {
+        dirs.push(PathBuf::from(home).join(".terminfo"));
+    }
+    let system = [
+        "/etc/terminfo",
+        "/lib/terminfo",
+        "/usr/share/terminfo",
+        "/usr/lib/terminfo",
+    ];
+    match std::env::var("TERMINFO_DIRS") {
+        // An empty entry means the system ones.
+        Ok(list) => {
+            for dir in list.split(':') {
+                match dir {
+                    "" => dirs.extend(system.iter().map(PathBuf::from)),
+                    _ => dirs.push(dir.into()),
+                }
+            }
+        }
+        Err(_) => dirs.extend(system.iter().map(PathBuf::from)),
+    }
+    dirs
+}
+
+/// Fill in a capability's parameters, like `tparm` does. Only numeric
+/// parameters are supported (`%s` prints them in decimal), and padding like
+/// `$<5>` is dropped, since nothing that'd run this needs it.
+pub fn expand(cap: &[u8], params: &[i32]) -> Vec<u8> {
+    let mut params = {
+        let mut p = [0; 9];
+        for (p, &v) in p.iter_mut().zip(params) {
+            *p = v;
+        }
+        p
+    };
+    let mut out = vec![];
+    let mut stack: Vec<i32> = vec![];
+    let mut vars = [0; 52];
+    let var = |c: u8| match c {
+        b'a'..=b'z' => Some((c - b'a') as usize),
+        b'A'..=b'Z' => Some((c - b'A') as usize + 26),
+        _ => None,
+    };
+    let mut i = 0;
+    while i < cap.len() {
+        let c = cap[i];
+        i += 1;
+        if c == b'$' && cap.get(i) == Some(&b'<') {
+            if let Some(end) = cap[i..].iter().position(|&b| b == b'>') {
+                i += end + 1;
+                continue;
+            }
+        }
+        if c != b'%' {
+            out.push(c);
+            continue;
+        }
+        let op = match cap.get(i) {
+            Some(&op) => op,
+            None => break,
+        };
+        i += 1;
+        match op {
+            b'%' => out.push(b'%'),
+            b'c' => out.push(pop(&mut stack) as u8),
+            b'p' => {
+                let n = cap.get(i).map_or(0, |d| d.wrapping_sub(b'1') as usize);
+                stack.push(params.get(n).copied().unwrap_or(0));
+                i += 1;
+            }
+            b'P' | b'g' => {
+                if let Some(v) = cap.get(i).and_then(|&c| var(c)) {
+                    if op == b'P' {
+                        vars[v] = pop(&mut stack);
+                    } else {
+                        stack.push(vars[v]);
+                    }
+                }
+                i += 1;
+            }
+            b'\'' => {
+                stack.push(cap.get(i).copied().unwrap_or(0) as i32);
+                // The character, then the closing quote.
+                i += 2;
+            }
+            b'{' => {
+                let end = cap[i..]
+                    .iter()
+                    .position(|&b| b == b'}')
+                    .unwrap_or(cap.len() - i);
+                let n = std::str::from_utf8(&cap[i..i + end]).ok();
+                stack.push(n.and_then(|n| n.parse().ok()).unwrap_or(0));
+                i += end + 1;
+            }
+            b'l' => {
+                // Strings are numbers here, so this is how long it'd print.
+                let n = pop(&mut stack).to_string().len();
+                stack.push(n as i32);
+            }
+            b'+' | b'-' | b'*' | b'/' | b'm' | b'&' | b'|' | b'^' | b'=' | b'>' | b'<' | b'A'
+            | b'O' => {
+                let (b, a) = (pop(&mut stack), pop(&mut stack));
+                stack.push(match op {
+                    b'+' => a.wrapping_add(b),
+                    b'-' => a.wrapping_sub(b),
+                    b'*' => a.wrapping_mul(b),
+                    b'/' => a.checked_div(b).unwrap_or(0),
+                    b'm' => a.checked_rem(b).unwrap_or(0),
+                    b'&' => a & b,
+                    b'|' => a | b,
+                    b'^' => a ^ b,
+                    b'=' => (a == b) as i32,
+                    b'>' => (a > b) as i32,
+                    b'<' => (a < b) as i32,
+                    b'A' => (a != 0 && b != 0) as i32,
+                    _ => (a != 0 || b != 0) as i32,
+                });
+            }
+            b'!' => {
+                let a = pop(&mut stack);
+                stack.push((a == 0) as i32);
+            }
+            b'~' => {
+                let a = pop(&mut stack);
+                stack.push(!a);
+            }
+            b'i' => {
+                params[0] += 1;
+                params[1] += 1;
+            }
+            b'?' | b';' => {}
+            b't' => {
+                if pop(&mut stack) == 0 {
+                    // Skip to the `%e` or `%;` that goes with this.
+                    i = skip_branch(cap, i, true);
+                }
+            }
+            // Only reached after running the `%t` branch, so skip the rest.
+            b'e' => i = skip_branch(cap, i, false),
+            _ => {
+                // A printf-style conversion: `%[[:]flags][width[.precision]]x`.
+                let start = i - 1;
+                let end = start
+                    + cap[start..]
+                        .iter()
+                        .position(|b| b"doxXs".contains(b))
+                        .unwrap_or(cap.len() - start);
+                let spec = &cap[start..end];
+                i = end + 1;
+                let n = pop(&mut stack);
+                out.extend(format_number(
+                    spec,
+                    cap.get(end).copied().unwrap_or(b'd'),
+                    n,
+                ));
+            }
+        }
+    }
+    out
+}
+
+fn pop(stack: &mut Vec<i32>) -> i32 {
+    stack.pop().unwrap_or(0)
+}
+
+/// Where the `%e` (when `to_else`) or `%;` ending the branch that starts at
+/// `i` is, counting nested `%?`s.
+fn skip_branch(cap: &[u8], mut i: usize, to_else: bool) -> usize {
+    let mut depth = 0;
+    while i + 1 < cap.len() {
+        if cap[i] != b'%' {
+            i += 1;
+            continue;
+        }
+        match cap[i + 1] {
+            b'?' => depth += 1,
+            b';' if depth == 0 => return i + 2,
+            b';' => depth -= 1,
+            b'e' if depth == 0 && to_else => return i + 2,
+            _ => {}
+        }
+        i += 2;
+    }
+    cap.len()
+}
+
+/// `n` formatted like `printf` would with `%{spec}{conv}`, where `spec` is the
+/// flags, width and precision.
+fn format_number(spec: &[u8], conv: u8, n: i32) -> Vec<u8> {
+    let spec = std::str::from_utf8(spec).unwrap_or_default();
+    let spec = spec.strip_prefix(':').unwrap_or(spec);
+    let flags_end = spec.find(|c| !"-+# ".contains(c)).unwrap_or(spec.len());
+    let (flags, spec) = spec.split_at(flags_end);
+    let zero = spec.starts_with('0');
+    let (width, precision) = match spec.split_once('.') {
+        Some((w, p)) => (w, p.parse().ok()),
+        None => (spec, None),
+    };
+    let width = width.parse::<usize>().unwrap_or(0);
+    let mut digits = match conv {
+        b'o' => format!("{:o}", n),
+        b'x' => format!("{:x}", n),
+        b'X' => format!("{:X}", n),
+        _ => n.unsigned_abs().to_string(),
+    };
+    if let Some(p) = precision {
+        while digits.len() < p {
+            digits.insert(0, '0');
+        }
+    }
+    let sign = match (conv, n < 0, flags) {
+        (b'd' | b's', true, _) => "-",
+        (b'd' | b's', false, f) if f.contains('+') => "+",
+        (b'd' | b's', false, f) if f.contains(' ') => " ",
+        _ => "",
+    };
+    let prefix = match conv {
+        b'o' if flags.contains('#') && !digits.starts_with('0') => "0",
+        b'x' if flags.contains('#') && n != 0 => "0x",
+        b'X' if flags.contains('#') && n != 0 => "0X",
+        _ => "",
+    };
+    let len = sign.len() + prefix.len() + digits.len();
+    let pad = width.saturating_sub(len);
+    let s = if flags.contains('-') {
+        format!("{}{}{}{}", sign, prefix, digits, " ".repeat(pad))
+    } else if zero && precision.is_none() {
+        format!("{}{}{}{}", sign, prefix, "0".repeat(pad), digits)
+    } else {
+        format!("{}{}{}{}", " ".repeat(pad), sign, prefix, digits)
+    };
+    s.into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::expand;
+
+    fn expand_str(cap: &str, params: &[i32]) -> String {
+        String::from_utf8(expand(cap.as_bytes(), params)).unwrap()
+    }
+
+    #[test]
+    fn params() {
+        // xterm's `cup`.
+        assert_eq!(expand_str("\x1b[%i%p1%d;%p2%dH", &[4, 9]), "\x1b[5;10H");
+        // The other way round, and missing ones are 0.
+        assert_eq!(expand_str("%p2%d,%p1%d,%p3%d", &[1, 2]), "2,1,0");
+        assert_eq!(expand_str("%p1%c", &[b'A' as i32]), "A");
+        assert_eq!(expand_str("100%%", &[]), "100%");
+    }
+
+    #[test]
+    fn conditionals() {
+        let cap = "%?%p1%{8}%<%t3%p1%d%e%p1%{16}%<%t9%p1%{8}%-%d%e38;5;%p1%d%;";
+        assert_eq!(expand_str(cap, &[1]), "31");
+        assert_eq!(expand_str(cap, &[12]), "94");
+        assert_eq!(expand_str(cap, &[200]), "38;5;200");
+        // Without an `%e`.
+        assert_eq!(expand_str("a%?%p1%tb%;c", &[0]), "ac");
+        assert_eq!(expand_str("a%?%p1%tb%;c", &[1]), "abc");
+        // Nested.
+        let cap = "%?%p1%t%?%p2%tx%ey%;%ez%;";
+        assert_eq!(expand_str(cap, &[1, 1]), "x");
+        assert_eq!(expand_str(cap, &[1, 0]), "y");
+        assert_eq!(expand_str(cap, &[0, 1]), "z");
+    }
+
+    #[test]
+    fn arithmetic_and_variables() {
+        assert_eq!(expand_str("%p1%p2%+%d", &[3, 4]), "7");
+        assert_eq!(expand_str("%p1%{10}%/%d%p1%{10}%m%d", &[42]), "42");
+        assert_eq!(expand_str("%p1%Pa%ga%ga%*%d", &[5]), "25");
+        assert_eq!(expand_str("%'x'%c", &[]), "x");
+        // Dividing by 0 doesn't panic.
+        assert_eq!(expand_str("%p1%{0}%/%d", &[5]), "0");
+    }
+
+    #[test]
+    fn formatting() {
+        assert_eq!(expand_str("%p1%03d", &[7]), "007");
+        assert_eq!(expand_str("%p1%x", &[255]), "ff");
+        assert_eq!(expand_str("%p1%:-3d|", &[7]), "7  |");
+        assert_eq!(expand_str("%p1%s", &[-12]), "-12");
+    }
+
+    #[test]
+    fn padding_is_dropped() {
+        assert_eq!(expand_str("\x1b[H$<5>x", &[]), "\x1b[Hx");
+    }
+}
@@ -0,0 +1,190 @@
+//! Cutting text down to a number of columns, with an ellipsis where the text
+//! was cut out.
+//!
+//! Cuts only happen between grapheme clusters. If that leaves the result a
+//! column short (say, because the next character was 2 wide and only 1 column
+//! was left), it's padded with a space so it always comes out exactly the
+//! width asked for. Escape sequences (see [`crate::ansi`]) are all kept, even
+//! from the part that was cut, so styling that's turned on or off there still
+//! is.
+use crate::{ansi, WidthStrategy};
+use std::borrow::Cow;
+use unicode_segmentation::UnicodeSegmentation;
+
+pub const ELLIPSIS: &str = "\u{2026}";
+
+/// Which part of the text to keep.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Keep {
+    Start,
+    Middle,
+    End,
+}
+
+/// Cut the end off `s` if it's wider than `width`, e.g. `abcd…`.
+pub fn truncate_end<'a>(s: &'a str, width: usize, strat: &dyn WidthStrategy) -> Cow<'a, str> {
+    truncate(s, width, strat, Keep::Start)
+}
+
+/// Cut the start off `s` if it's wider than `width`, e.g. `…wxyz`.
+pub fn truncate_start<'a>(s: &'a str, width: usize, strat: &dyn WidthStrategy) -> Cow<'a, str> {
+    truncate(s, width, strat, Keep::End)
+}
+
+/// Cut the middle out of `s` if it's wider than `width`, e.g. `ab…yz`.
+pub fn truncate_middle<'a>(s: &'a str, width: usize, strat: &dyn WidthStrategy) -> Cow<'a, str> {
+    truncate(s, width, strat, Keep::Middle)
+}
+
+enum Unit<'a> {
+    Control(&'a str),
+    Grapheme(&'a str, usize),
+}
+
+fn truncate<'a>(s: &'a str, width: usize, strat: &dyn WidthStrategy, keep: Keep) -> Cow<'a, str> {
+    if strat.str_width(&ansi::strip(s)) <= width {
+        return Cow::Borrowed(s);
+    }
+    let units = ansi::tokenize(s)
+        .flat_map(|t| match t {
+            ansi::Token::Control(c) => vec![Unit::Control(c)],
+            ansi::Token::Text(t) => t
+                .graphemes(true)
+                .map(|g| Unit::Grapheme(g, strat.str_width(g)))
+                .collect(),
+        })
+        .collect::<Vec<_>>();
+    let ellipsis = Some(strat.str_width(ELLIPSIS)).filter(|&w| w <= width);
+    let budget = width - ellipsis.unwrap_or_default();
+
+    // Mark the graphemes to keep, from the front and then from the back.
+    let mut kept = vec![false; units.len()];
+    let front_budget = match keep {
+        Keep::Start => budget,
+        Keep::Middle => budget.div_ceil(2),
+        Keep::End => 0,
+    };
+    let mut used = take_while_fits(units.iter().enumerate(), front_budget, &mut kept);
+    if keep != Keep::Start {
+        used += take_while_fits(units.iter().enumerate().rev(), budget - used, &mut kept);
+    }
+
+    let mut out = String::with_capacity(s.len());
+    let pad = " ".repeat(budget - used);
+    if keep == Keep::End {
+        out.push_str(&pad);
+    }
+    let mut cut = false;
+    for (unit, &kept) in units.iter().zip(&kept) {
+        match unit {
+            Unit::Control(c) => out.push_str(c),
+            Unit::Grapheme(g, _) if kept => out.push_str(g),
+            Unit::Grapheme(..) if !cut => {
+                cut = true;
+                if ellipsis.is_some() {
+                    out.push_str(ELLIPSIS);
+                }
+            }
+            Unit::Grapheme(..) => {}
+        }
+    }
+    if keep != Keep::End {
+        out.push_str(&pad);
+    }
+    Cow::Owned(out)
+}
+
+/// Mark graphemes from `units` as kept until the next one doesn't fit in
+/// `budget`, and return how many columns they use.
+fn take_while_fits<'u, 'a: 'u>(
+    units: impl Iterator<Item = (usize, &'u Unit<'a>)>,
+    budget: usize,
+    kept: &mut [bool],
+) -> usize {
+    let mut used = 0;
+    for (i, unit) in units {
+        if let Unit::Grapheme(_, w) = *unit {
+            if used + w > budget {
+                break;
+            }
+            used += w;
+            kept[i] = true;
+        }
+    }
+    used
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::strategies::UnicodeWidth;
+
+    #[test]
+    fn fits() {
+        assert!(matches!(
+            truncate_end("abc", 3, &UnicodeWidth::default()),
+            Cow::Borrowed("abc")
+        ));
+        assert_eq!(truncate_middle("", 0, &UnicodeWidth::default()), "");
+    }
+
+    #[test]
+    fn each_end() {
+        assert_eq!(
+            truncate_end("abcdefgh", 5, &UnicodeWidth::default()),
+            "abcd…"
+        );
+        assert_eq!(
+            truncate_start("abcdefgh", 5, &UnicodeWidth::default()),
+            "…efgh"
+        );
+        assert_eq!(
+            truncate_middle("abcdefgh", 5, &UnicodeWidth::default()),
+            "ab…gh"
+        );
+        assert_eq!(
+            truncate_middle("abcdefgh", 6, &UnicodeWidth::default()),
+            "abc…gh"
+        );
+    }
+
+    #[test]
+    fn zero_width() {
+        // No room even for the ellipsis.
+        assert_eq!(truncate_end("abc", 0, &UnicodeWidth::default()), "");
+        assert_eq!(truncate_end("abc", 1, &UnicodeWidth::default()), "…");
+    }
+
+    #[test]
+    fn cjk_padding() {
+        // `字` would go one column over, so it's a space instead.
+        assert_eq!(
+            truncate_end("漢字漢字", 4, &UnicodeWidth::default()),
+            "漢… "
+        );
+        assert_eq!(
+            truncate_start("漢字漢字", 4, &UnicodeWidth::default()),
+            " …字"
+        );
+        assert_eq!(
+            truncate_middle("漢字漢字", 6, &UnicodeWidth::default()),
+            "漢…字 "
+        );
+    }
+
+    #[test]
+    fn graphemes_stay_whole() {
+        assert_eq!(
+            truncate_end("e\u{301}e\u{301}e\u{301}", 2, &UnicodeWidth::default()),
+            "e\u{301}…"
+        );
+    }
+
+    #[test]
+    fn escapes_are_kept() {
+        assert_eq!(
+            truncate_end("\x1b[1mabc\x1b[0mdef", 3, &UnicodeWidth::default()),
+            "\x1b[1mab…\x1b[0m"
+        );
+    }
+}
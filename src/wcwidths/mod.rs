@@ -1,7 +1,105 @@
 // Port of https://github.com/ridiculousfish/widecharwidth/
+mod private_use;
+mod tables;
 mod widecharwidth;
+pub use private_use::{NotPrivateUse, PrivateUseWidths};
+pub use tables::UNICODE_VERSION;
+pub use widecharwidth::age;
 use widecharwidth::WCWidth;
 
+/// A Unicode version, as `major.minor`. The update number never matters for
+/// widths.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct UnicodeVersion(pub u8, pub u8);
+
+impl UnicodeVersion {
+    /// The version our tables are from, and the newest one they can answer
+    /// for. Older ones only differ in which characters are assigned, and in
+    /// the emoji Unicode 9 widened. Changes to other characters' East Asian
+    /// Width aren't tracked.
+    pub const LATEST: Self = Self(UNICODE_VERSION.0, UNICODE_VERSION.1);
+}
+
+impl Default for UnicodeVersion {
+    fn default() -> Self {
+        Self::LATEST
+    }
+}
+
+impl std::fmt::Display for UnicodeVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}", self.0, self.1)
+    }
+}
+
+impl std::str::FromStr for UnicodeVersion {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, String> {
+        let bad = || format!("bad unicode version {:?}, expected e.g. `9.0`", s);
+        let mut parts = s.trim().split('.');
+        let major = parts.next().and_then(|p| p.parse().ok()).ok_or_else(bad)?;
+        let minor = match parts.next() {
+            Some(p) => p.parse().map_err(|_| bad())?,
+            None => 0,
+        };
+        // Allow (and ignore) an update number, like `12.1.0`.
+        if parts.next().is_some_and(|p| p.parse::<u8>().is_err()) || parts.next().is_some() {
+            return Err(bad());
+        }
+        let version = Self(major, minor);
+        if version > Self::LATEST {
+            return Err(format!(
+                "unicode {} is newer than the tables, which only go up to {}",
+                version,
+                Self::LATEST
+            ));
+        }
+        Ok(version)
+    }
+}
+
+/// How wide characters with East Asian Width "Ambiguous" are. Terminals
+/// default to narrow, but usually have a setting to make them wide, which CJK
+/// users tend to turn on.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum AmbiguousWidth {
+    #[default]
+    Narrow,
+    Wide,
+}
+
+impl AmbiguousWidth {
+    pub fn width(self) -> usize {
+        match self {
+            AmbiguousWidth::Narrow => 1,
+            AmbiguousWidth::Wide => 2,
+        }
+    }
+}
+
+impl std::fmt::Display for AmbiguousWidth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            AmbiguousWidth::Narrow => "narrow",
+            AmbiguousWidth::Wide => "wide",
+        })
+    }
+}
+
+impl std::str::FromStr for AmbiguousWidth {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "narrow" => Ok(AmbiguousWidth::Narrow),
+            "wide" => Ok(AmbiguousWidth::Wide),
+            _ => Err(format!(
+                "bad ambiguous width {:?}, expected `narrow` or `wide`",
+                s
+            )),
+        }
+    }
+}
+
 pub fn init_once() {
     static LOCALE_INIT: std::sync::Once = std::sync::Once::new();
     LOCALE_INIT.call_once(|| unsafe {
@@ -10,7 +108,7 @@ pub fn init_once() {
         // fetch current locale
         let cur = core::ptr::NonNull::new(libc::setlocale(libc::LC_ALL, core::ptr::null()));
         // Detect if it's utf8
-        let change = cur.map_or(true, |p| {
+        let change = cur.is_none_or(|p| {
             let cstr = std::ffi::CStr::from_ptr(p.as_ptr());
             let locale = cstr.to_string_lossy().to_ascii_lowercase();
             !locale.contains("utf-8") && !locale.contains("utf8")
@@ -24,6 +122,16 @@ pub fn init_once() {
     });
 }
 
+/// The locale [`init_once`] ended up with, e.g. `en_US.UTF-8`.
+pub fn locale() -> Option<String> {
+    init_once();
+    unsafe {
+        let cur = core::ptr::NonNull::new(libc::setlocale(libc::LC_ALL, core::ptr::null()))?;
+        let cstr = std::ffi::CStr::from_ptr(cur.as_ptr());
+        Some(cstr.to_string_lossy().into_owned())
+    }
+}
+
 mod lib_c {
     // Surprisingly, these aren't in the libc crate.
     extern "C" {
@@ -41,11 +149,21 @@ pub fn system_wcwidth(c: char) -> Result<usize, libc::c_int> {
 
 /// widecharwidth_wcwidth with the settings recommended on it's github page
 pub fn widecharwidth_recommended(c: char) -> usize {
-    match widecharwidth::wcwidth(c) {
+    widecharwidth_recommended_in(c, UnicodeVersion::LATEST, AmbiguousWidth::Narrow)
+}
+
+/// [`widecharwidth_recommended`] as of Unicode `version`, with ambiguous
+/// characters `ambiguous` wide.
+pub fn widecharwidth_recommended_in(
+    c: char,
+    version: UnicodeVersion,
+    ambiguous: AmbiguousWidth,
+) -> usize {
+    match widecharwidth::wcwidth_in(c, version) {
         WCWidth::Width(n) => n,
         WCWidth::Nonprint => 0,
         WCWidth::Combining => 0,
-        WCWidth::Ambiguous => 1,
+        WCWidth::Ambiguous => ambiguous.width(),
         WCWidth::PrivateUse => 1,
         WCWidth::Unassigned => 0,
         WCWidth::WidenedIn9 => 2,
@@ -54,17 +172,26 @@ pub fn widecharwidth_recommended(c: char) -> usize {
 
 /// equivalent to what fish does on my machine
 pub fn widecharwidth_fish(c: char) -> usize {
+    widecharwidth_fish_in(c, UnicodeVersion::LATEST, AmbiguousWidth::Narrow)
+}
+
+/// [`widecharwidth_fish`] as of Unicode `version`, with ambiguous characters
+/// `ambiguous` wide.
+pub fn widecharwidth_fish_in(c: char, version: UnicodeVersion, ambiguous: AmbiguousWidth) -> usize {
     match c {
         // VS16 emoji selection
         '\u{fe0f}' => 1,
         '\u{fe0e}' => 0,
         // Korean Hangul Jamo median vowels and final consonants
         '\u{1160}'..='\u{11ff}' => 0,
-        _ => match widecharwidth::wcwidth(c) {
+        _ => match widecharwidth::wcwidth_in(c, version) {
             WCWidth::Width(n) => n,
-            WCWidth::Ambiguous => 1,
+            WCWidth::Ambiguous => ambiguous.width(),
             WCWidth::PrivateUse => 1,
             WCWidth::WidenedIn9 => 2,
+            // Assigned, just not yet as of `version`, so libc probably knows
+            // about it even though the terminal we're pretending to be doesn't.
+            WCWidth::Unassigned if age(c).is_some() => 0,
             WCWidth::Nonprint | WCWidth::Combining | WCWidth::Unassigned => {
                 system_wcwidth(c).unwrap_or_default()
             }
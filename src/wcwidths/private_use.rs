@@ -0,0 +1,140 @@
+//! Widths for private use characters, which widecharwidth always calls 1.
+//!
+//! Icon fonts like Nerd Fonts put their glyphs in the private use area, and a
+//! lot of terminals draw those 2 wide, either because they're configured to or
+//! because they size glyphs by how wide they actually are in the font.
+//! Overrides use the same line format as a [`Profile`], minus sequences, and
+//! can only cover characters in `WIDECHAR_PRIVATE_TABLE`:
+//!
+//! ```text
+//! # Font Awesome
+//! ed00-f2ff 2
+//! ```
+use super::widecharwidth::is_private_use_range;
+use crate::profile::{ParseProfileError, Profile};
+use std::fmt;
+
+/// Widths for (parts of) the private use area. Anything without an override
+/// keeps widecharwidth's width of 1.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PrivateUseWidths {
+    widths: Profile,
+}
+
+/// Returned when trying to override something outside the private use area.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NotPrivateUse {
+    pub lo: u32,
+    pub hi: u32,
+}
+
+impl fmt::Display for NotPrivateUse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.lo == self.hi {
+            write!(f, "U+{:04X} isn't in the private use area", self.lo)
+        } else {
+            write!(
+                f,
+                "U+{:04X}..U+{:04X} isn't all in the private use area",
+                self.lo, self.hi
+            )
+        }
+    }
+}
+
+impl std::error::Error for NotPrivateUse {}
+
+/// `(start, end, width)` for the glyph sets Nerd Fonts 3 adds to the private
+/// use area, from its glyph set table. Its non-private ones (the octicons at
+/// U+2665 and U+26A1, IEC power symbols, box drawing) are left alone.
+const NERD_FONTS: &[(u32, u32, usize)] = &[
+    // Pomicons
+    (0xE000, 0xE00A, 2),
+    // Powerline and Powerline Extra. These are meant to join up with the
+    // cells around them, so they're 1 wide even in terminals that widen
+    // everything else.
+    (0xE0A0, 0xE0A3, 1),
+    (0xE0B0, 0xE0D7, 1),
+    // Font Awesome Extension
+    (0xE200, 0xE2A9, 2),
+    // Weather
+    (0xE300, 0xE3E3, 2),
+    // Seti-UI and custom
+    (0xE5FA, 0xE6B7, 2),
+    // Devicons
+    (0xE700, 0xE8EF, 2),
+    // Codicons
+    (0xEA60, 0xEC1E, 2),
+    // Font Awesome
+    (0xED00, 0xF2FF, 2),
+    // Progress indicators, which tile like the Powerline symbols.
+    (0xEE00, 0xEE0B, 1),
+    // Font Logos
+    (0xF300, 0xF381, 2),
+    // Octicons
+    (0xF400, 0xF533, 2),
+    // Material Design
+    (0xF0001, 0xF1AF0, 2),
+];
+
+impl PrivateUseWidths {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Nerd Font icons as 2 wide, and Powerline symbols as 1 wide.
+    pub fn nerd_fonts() -> Self {
+        let mut widths = Self::new();
+        for &(lo, hi, width) in NERD_FONTS {
+            widths.widths.insert_range(lo, hi, width);
+        }
+        widths
+    }
+
+    /// Parse overrides in the format described in the [module docs](self).
+    pub fn parse(src: &str) -> Result<Self, ParseProfileError> {
+        let mut widths = Self::new();
+        for (i, line) in src.lines().enumerate() {
+            let err = |msg: String| ParseProfileError { line: i + 1, msg };
+            // Each line on its own is a valid profile, so let that do the
+            // parsing.
+            let parsed = Profile::parse(line).map_err(|e| err(e.msg))?;
+            if parsed.sequences().next().is_some() {
+                return Err(err("sequences can't be overridden".into()));
+            }
+            for (lo, hi, width) in parsed.ranges() {
+                widths
+                    .insert_range(lo, hi, width)
+                    .map_err(|e| err(e.to_string()))?;
+            }
+        }
+        Ok(widths)
+    }
+
+    pub fn load(
+        path: impl AsRef<std::path::Path>,
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(Self::parse(&std::fs::read_to_string(path)?)?)
+    }
+
+    /// Make `lo..=hi` `width` wide, replacing any earlier overrides there.
+    pub fn insert_range(&mut self, lo: u32, hi: u32, width: usize) -> Result<(), NotPrivateUse> {
+        if !is_private_use_range(lo, hi) {
+            return Err(NotPrivateUse { lo, hi });
+        }
+        self.widths.insert_range(lo, hi, width);
+        Ok(())
+    }
+
+    /// Add `other`'s overrides on top of these.
+    pub fn extend(&mut self, other: &PrivateUseWidths) {
+        for (lo, hi, width) in other.widths.ranges() {
+            self.widths.insert_range(lo, hi, width);
+        }
+    }
+
+    /// The overridden width for `c`, if it has one.
+    pub fn width(&self, c: char) -> Option<usize> {
+        self.widths.char_width(c)
+    }
+}
@@ -0,0 +1,220 @@
+//! Word wrapping that measures with any [`WidthStrategy`], breaking at the
+//! line break opportunities from [UAX #14].
+//!
+//! Words too long for a line are split between grapheme clusters, never
+//! inside one, and a wide character that would straddle the right margin
+//! moves to the next line instead. Escape sequences (see [`crate::ansi`]) are
+//! kept whole, but don't count towards the width or change where lines break.
+//!
+//! [UAX #14]: https://unicode.org/reports/tr14
+use crate::{ansi, WidthStrategy};
+use unicode_linebreak::BreakOpportunity;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Settings for [`wrap_with`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Options {
+    /// Columns each line can use, including any indent.
+    pub width: usize,
+    /// Columns to indent every line but the first by.
+    pub hanging_indent: usize,
+}
+
+impl Options {
+    pub fn new(width: usize) -> Self {
+        Self {
+            width,
+            hanging_indent: 0,
+        }
+    }
+}
+
+/// Wrap `text` to lines at most `width` columns wide, as measured by `strat`.
+/// Lines only go over when a single grapheme is wider than `width`.
+pub fn wrap(text: &str, width: usize, strat: &dyn WidthStrategy) -> Vec<String> {
+    wrap_with(text, &Options::new(width), strat)
+}
+
+/// [`wrap`], with more settings.
+pub fn wrap_with(text: &str, opts: &Options, strat: &dyn WidthStrategy) -> Vec<String> {
+    let mut w = Wrapper {
+        strat,
+        opts,
+        lines: vec![],
+        line: String::new(),
+        width: 0,
+        empty: true,
+    };
+    // Find the breaks in the text without its escape sequences, so that
+    // there's never one inside a sequence, then map them back. A sequence
+    // right before a break goes with the text after it.
+    let mut plain = String::new();
+    let mut texts = vec![];
+    let mut offset = 0;
+    for token in ansi::tokenize(text) {
+        match token {
+            ansi::Token::Control(c) => offset += c.len(),
+            ansi::Token::Text(t) => {
+                texts.push((plain.len(), offset));
+                plain.push_str(t);
+                offset += t.len();
+            }
+        }
+    }
+    let breaks = unicode_linebreak::linebreaks(&plain).map(|(end, opportunity)| {
+        if end == plain.len() {
+            return (text.len(), opportunity);
+        }
+        let (plain_start, start) = texts[texts.partition_point(|&(p, _)| p <= end) - 1];
+        (start + end - plain_start, opportunity)
+    });
+    let mut start = 0;
+    for (end, opportunity) in breaks {
+        let segment = &text[start..end];
+        start = end;
+        // A segment is a word, then whatever whitespace follows it, which is
+        // allowed to hang past the margin.
+        let word = segment.trim_end();
+        let space = &segment[word.len()..];
+        w.push_word(word);
+        if opportunity == BreakOpportunity::Mandatory {
+            w.finish_line();
+        } else {
+            w.push_str(space);
+        }
+    }
+    if !w.empty {
+        w.finish_line();
+    }
+    w.lines
+}
+
+struct Wrapper<'a> {
+    strat: &'a dyn WidthStrategy,
+    opts: &'a Options,
+    lines: Vec<String>,
+    line: String,
+    /// Of `line`, including the indent.
+    width: usize,
+    /// Whether `line` is just the indent.
+    empty: bool,
+}
+
+impl Wrapper<'_> {
+    fn measure(&self, s: &str) -> usize {
+        self.strat.str_width(&ansi::strip(s))
+    }
+
+    fn push_str(&mut self, s: &str) {
+        self.width += self.measure(s);
+        self.line.push_str(s);
+        self.empty = false;
+    }
+
+    fn push_word(&mut self, word: &str) {
+        let width = self.measure(word);
+        if self.width + width <= self.opts.width {
+            self.push_str(word);
+            return;
+        }
+        if !self.empty {
+            self.finish_line();
+            if self.width + width <= self.opts.width {
+                self.push_str(word);
+                return;
+            }
+        }
+        // Too long for a line of its own, so split it up, keeping escape
+        // sequences whole.
+        for token in ansi::tokenize(word) {
+            let text = match token {
+                ansi::Token::Control(c) => {
+                    self.line.push_str(c);
+                    continue;
+                }
+                ansi::Token::Text(t) => t,
+            };
+            for g in text.graphemes(true) {
+                let width = self.strat.str_width(g);
+                if !self.empty && self.width + width > self.opts.width {
+                    self.finish_line();
+                }
+                self.width += width;
+                self.line.push_str(g);
+                self.empty = false;
+            }
+        }
+    }
+
+    fn finish_line(&mut self) {
+        let line = std::mem::take(&mut self.line);
+        self.lines.push(line.trim_end().to_string());
+        let indent = self.opts.hanging_indent;
+        self.line = " ".repeat(indent);
+        self.width = indent;
+        self.empty = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::strategies::UnicodeWidth;
+
+    fn wrap(text: &str, width: usize) -> Vec<String> {
+        super::wrap(text, width, &UnicodeWidth::default())
+    }
+
+    #[test]
+    fn words() {
+        assert_eq!(wrap("the quick brown fox", 10), ["the quick", "brown fox"]);
+        assert_eq!(wrap("a\nb c", 10), ["a", "b c"]);
+        assert_eq!(wrap("", 10), Vec::<String>::new());
+    }
+
+    #[test]
+    fn long_words_split_between_graphemes() {
+        assert_eq!(wrap("abcdefg", 3), ["abc", "def", "g"]);
+        // The accent stays with its `e`.
+        assert_eq!(wrap("ee\u{301}e", 1), ["e", "e\u{301}", "e"]);
+    }
+
+    #[test]
+    fn wide_characters_dont_straddle_the_margin() {
+        assert_eq!(wrap("a漢字", 4), ["a漢", "字"]);
+        assert_eq!(wrap("漢字漢", 3), ["漢", "字", "漢"]);
+    }
+
+    #[test]
+    fn zero_width() {
+        // Too narrow for anything, so one grapheme per line.
+        assert_eq!(wrap("ab", 0), ["a", "b"]);
+        assert_eq!(wrap("a\u{200b}b", 1), ["a\u{200b}", "b"]);
+    }
+
+    #[test]
+    fn hanging_indent() {
+        let opts = Options {
+            width: 6,
+            hanging_indent: 2,
+        };
+        assert_eq!(
+            wrap_with("one two three", &opts, &UnicodeWidth::default()),
+            ["one", "  two", "  thre", "  e"]
+        );
+    }
+
+    #[test]
+    fn escapes_dont_count() {
+        assert_eq!(wrap("\x1b[1mab\x1b[0m cd", 2), ["\x1b[1mab\x1b[0m", "cd"]);
+        assert_eq!(
+            wrap("\x1b[31mabcdefgh\x1b[0m", 3),
+            ["\x1b[31mabc", "def", "gh\x1b[0m"]
+        );
+        let link = "\x1b]8;;https://example.com\x1b\\abcdef\x1b]8;;\x1b\\";
+        assert_eq!(
+            wrap(link, 4),
+            ["\x1b]8;;https://example.com\x1b\\abcd", "ef\x1b]8;;\x1b\\"]
+        );
+    }
+}
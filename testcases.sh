@@ -1,4 +1,8 @@
 #!/usr/bin/env bash
 cargo run -- 'abc' 'lööps' 'ＡＢＣＤ' 'ᄀ' '각' 'ᄀᄀᄀ각ᆨᆨ' '👨‍👩‍👦‍👦' '🏳️‍🌈' '🇦🇶' 'Z̮̞̠͙͔ͅḀ̗̞͈̻̗Ḷ͙͎̯̹̞͓G̻O̭̗̮' '﷽'
 
-# cargo run -- 'Z̮̞̠͙͔ͅḀ̗̞͈̻̗Ḷ͙͎̯̹̞͓G̻O̭̗̮' "$(printf "\e[32;1m%s\e[m" "this one is probably cheating")"
+cargo run -- 'Z̮̞̠͙͔ͅḀ̗̞͈̻̗Ḷ͙͎̯̹̞͓G̻O̭̗̮' "$(printf "\e[32;1m%s\e[m" "this one is probably cheating")"
+
+cargo run -- -t unicode_width -t bidi -t read_pos 'שלום world' 'مرحبا (123) hello' 'abc עברית def' 'ﷺ ﷽'
+
+cargo run -- --mode2027 on -t mode2027 -t variation_selectors -t read_pos '⌚︎' '⌚️' '⭐︎' '☕︎' '❤️' '👨‍👩‍👧'
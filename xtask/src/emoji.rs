@@ -0,0 +1,168 @@
+//! Reading the RGI emoji sequences out of `emoji-test.txt`, and the characters
+//! that take variation selectors out of `emoji-variation-sequences.txt`, for
+//! `src/emoji/sequences.rs`.
+use super::Result;
+use std::fmt::Write;
+use std::path::Path;
+
+pub struct Sequences {
+    version: (u8, u8),
+    /// Every RGI sequence that's more than one codepoint, sorted.
+    sequences: Vec<String>,
+    /// Every character with a text and emoji style variation sequence, sorted.
+    variation_bases: Vec<u32>,
+}
+
+/// Pull `15.1` out of a `# Version: 15.1` header line.
+fn file_version(src: &str, name: &str) -> Result<(u8, u8)> {
+    let bad = || format!("{}: couldn't find a `# Version:` line", name);
+    let v = src
+        .lines()
+        .find_map(|l| l.strip_prefix("# Version:"))
+        .ok_or_else(bad)?
+        .trim();
+    let (major, minor) = v.split_once('.').ok_or_else(bad)?;
+    Ok((
+        major.parse().map_err(|_| bad())?,
+        minor.parse().map_err(|_| bad())?,
+    ))
+}
+
+fn parse_cp(s: &str) -> Result<char> {
+    u32::from_str_radix(s, 16)
+        .ok()
+        .and_then(char::from_u32)
+        .ok_or_else(|| format!("bad codepoint {:?}", s).into())
+}
+
+/// The multi-codepoint sequences from lines like
+/// `1F469 200D 1F4BB ; fully-qualified # 👩‍💻 E4.0 woman technologist`. The
+/// fully-qualified ones are exactly the RGI set.
+fn parse_rgi_sequences(src: &str) -> Result<Vec<String>> {
+    let mut sequences = vec![];
+    for line in src.lines() {
+        let data = line.split('#').next().unwrap_or_default();
+        let (cps, status) = match data.split_once(';') {
+            Some((cps, status)) => (cps.trim(), status.trim()),
+            None => continue,
+        };
+        if status != "fully-qualified" {
+            continue;
+        }
+        let seq = cps
+            .split_whitespace()
+            .map(parse_cp)
+            .collect::<Result<String>>()?;
+        if seq.chars().count() > 1 {
+            sequences.push(seq);
+        }
+    }
+    Ok(sequences)
+}
+
+/// The bases from lines like `231A FE0E ; text style; # (1.1) WATCH`.
+fn parse_variation_bases(src: &str) -> Result<Vec<u32>> {
+    let mut bases = vec![];
+    for line in src.lines() {
+        let data = line.split('#').next().unwrap_or_default();
+        let cps = match data.split(';').next() {
+            Some(cps) if !cps.trim().is_empty() => cps.trim(),
+            _ => continue,
+        };
+        match cps.split_whitespace().collect::<Vec<_>>()[..] {
+            [base, "FE0E" | "FE0F"] => bases.push(parse_cp(base)? as u32),
+            _ => return Err(format!("bad variation sequence {:?}", cps).into()),
+        }
+    }
+    Ok(bases)
+}
+
+/// Collapse sorted codepoints into inclusive ranges.
+fn ranges(cps: &[u32]) -> Vec<(u32, u32)> {
+    let mut out: Vec<(u32, u32)> = vec![];
+    for &cp in cps {
+        match out.last_mut() {
+            Some(last) if last.1 + 1 == cp => last.1 = cp,
+            _ => out.push((cp, cp)),
+        }
+    }
+    out
+}
+
+impl Sequences {
+    pub fn load(dir: &Path) -> Result<Self> {
+        let read = |name: &str| {
+            let path = dir.join(name);
+            std::fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))
+        };
+        let test = read("emoji-test.txt")?;
+        let variations = read("emoji-variation-sequences.txt")?;
+        let version = file_version(&test, "emoji-test.txt")?;
+        if file_version(&variations, "emoji-variation-sequences.txt")? != version {
+            return Err("emoji-variation-sequences.txt is from a different emoji version".into());
+        }
+        let mut sequences = parse_rgi_sequences(&test)?;
+        sequences.sort_unstable();
+        sequences.dedup();
+        let mut variation_bases = parse_variation_bases(&variations)?;
+        variation_bases.sort_unstable();
+        variation_bases.dedup();
+        Ok(Self {
+            version,
+            sequences,
+            variation_bases,
+        })
+    }
+
+    pub fn version_string(&self) -> String {
+        format!("{}.{}", self.version.0, self.version.1)
+    }
+
+    pub fn render(&self) -> String {
+        let (major, minor) = self.version;
+        let mut out = format!(
+            "//! RGI emoji sequences, generated by `cargo xtask gen-emoji` from Emoji\n\
+             //! {}.{}'s emoji-test.txt and emoji-variation-sequences.txt. Don't edit\n\
+             //! by hand.\n\
+             \n\
+             /// Emoji version these were generated from.\n\
+             pub const EMOJI_VERSION: (u8, u8) = ({}, {});\n",
+            major, minor, major, minor,
+        );
+        out.push_str("\n/// Every RGI emoji sequence longer than one codepoint, sorted.\n");
+        out.push_str("#[rustfmt::skip]\n");
+        let _ = writeln!(
+            out,
+            "pub static RGI_SEQUENCES: [&str; {}] = [",
+            self.sequences.len()
+        );
+        for seq in &self.sequences {
+            let escaped = seq
+                .chars()
+                .map(|c| format!("\\u{{{:x}}}", c as u32))
+                .collect::<String>();
+            let _ = writeln!(out, "    \"{}\",", escaped);
+        }
+        out.push_str("];\n");
+        let ranges = ranges(&self.variation_bases);
+        out.push_str(
+            "\n/// Characters with both a text (VS15) and an emoji (VS16) variation\n\
+             /// sequence, as inclusive ranges.\n",
+        );
+        out.push_str("#[rustfmt::skip]\n");
+        let _ = writeln!(
+            out,
+            "pub static VARIATION_BASES: [(u32, u32); {}] = [",
+            ranges.len()
+        );
+        for row in ranges.chunks(3) {
+            let row = row
+                .iter()
+                .map(|(lo, hi)| format!("(0x{:05X}, 0x{:05X}),", lo, hi))
+                .collect::<Vec<_>>();
+            let _ = writeln!(out, "    {}", row.join(" "));
+        }
+        out.push_str("];\n");
+        out
+    }
+}
@@ -0,0 +1,75 @@
+//! Maintenance tasks that shouldn't be part of the normal build. Run with
+//! `cargo xtask <task>`.
+mod emoji;
+mod ucd;
+
+use std::path::PathBuf;
+type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
+type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(argh::FromArgs)]
+/// Maintenance tasks for term-width-blog-example.
+struct Args {
+    #[argh(subcommand)]
+    task: Task,
+}
+
+#[derive(argh::FromArgs)]
+#[argh(subcommand)]
+enum Task {
+    GenTables(GenTables),
+    GenEmoji(GenEmoji),
+}
+
+#[derive(argh::FromArgs)]
+#[argh(subcommand, name = "gen-tables")]
+/// Regenerate the widecharwidth tables from Unicode Character Database files.
+struct GenTables {
+    /// directory holding `EastAsianWidth.txt`, `UnicodeData.txt`,
+    /// `emoji-data.txt`, `DerivedGeneralCategory.txt` and `DerivedAge.txt`.
+    /// defaults to `ucd`.
+    #[argh(option, default = "PathBuf::from(\"ucd\")")]
+    ucd: PathBuf,
+    /// where to write the tables. defaults to `src/wcwidths/tables.rs`.
+    #[argh(option, default = "PathBuf::from(\"src/wcwidths/tables.rs\")")]
+    out: PathBuf,
+}
+
+#[derive(argh::FromArgs)]
+#[argh(subcommand, name = "gen-emoji")]
+/// Regenerate the RGI emoji sequence list from the emoji data files.
+struct GenEmoji {
+    /// directory holding `emoji-test.txt` and
+    /// `emoji-variation-sequences.txt`. defaults to `ucd`.
+    #[argh(option, default = "PathBuf::from(\"ucd\")")]
+    ucd: PathBuf,
+    /// where to write the list. defaults to `src/emoji/sequences.rs`.
+    #[argh(option, default = "PathBuf::from(\"src/emoji/sequences.rs\")")]
+    out: PathBuf,
+}
+
+fn main() -> Result<()> {
+    let args: Args = argh::from_env();
+    match args.task {
+        Task::GenTables(gen) => {
+            let data = ucd::Ucd::load(&gen.ucd)?;
+            let src = ucd::render(&data.tables());
+            std::fs::write(&gen.out, src)?;
+            println!(
+                "wrote {} (unicode {})",
+                gen.out.display(),
+                data.version_string()
+            );
+        }
+        Task::GenEmoji(gen) => {
+            let seqs = emoji::Sequences::load(&gen.ucd)?;
+            std::fs::write(&gen.out, seqs.render())?;
+            println!(
+                "wrote {} (emoji {})",
+                gen.out.display(),
+                seqs.version_string()
+            );
+        }
+    }
+    Ok(())
+}
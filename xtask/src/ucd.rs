@@ -0,0 +1,413 @@
+//! Reading the Unicode Character Database files, and turning them into the
+//! tables `src/wcwidths/widecharwidth.rs` uses. The classification follows
+//! widecharwidth's own `generate.py`.
+use super::Result;
+use std::fmt::Write;
+use std::path::Path;
+
+const MAX_CP: u32 = 0x10FFFF;
+
+/// One non-comment line of a UCD data file: the codepoint range, and the `;`
+/// separated fields after it.
+struct Line {
+    lo: u32,
+    hi: u32,
+    fields: Vec<String>,
+}
+
+fn parse_cp(s: &str) -> Result<u32> {
+    let n =
+        u32::from_str_radix(s.trim(), 16).map_err(|e| format!("bad codepoint {:?}: {}", s, e))?;
+    if n > MAX_CP {
+        return Err(format!("codepoint {:?} out of range", s).into());
+    }
+    Ok(n)
+}
+
+fn read(dir: &Path, name: &str) -> Result<String> {
+    let path = dir.join(name);
+    std::fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e).into())
+}
+
+fn parse_datafile(src: &str) -> Result<Vec<Line>> {
+    let mut lines = vec![];
+    for (i, line) in src.lines().enumerate() {
+        let data = line.split('#').next().unwrap_or_default();
+        if data.trim().is_empty() {
+            continue;
+        }
+        let mut fields = data.split(';').map(|f| f.trim().to_string());
+        let range = fields.next().unwrap_or_default();
+        let (lo, hi) = match range.split_once("..") {
+            Some((lo, hi)) => (parse_cp(lo)?, parse_cp(hi)?),
+            None => (parse_cp(&range)?, parse_cp(&range)?),
+        };
+        if lo > hi {
+            return Err(format!("line {}: backwards range {:?}", i + 1, range).into());
+        }
+        lines.push(Line {
+            lo,
+            hi,
+            fields: fields.collect(),
+        });
+    }
+    Ok(lines)
+}
+
+/// Pull `12.1.0` out of a header like `# EastAsianWidth-12.1.0.txt`.
+fn file_version(src: &str, name: &str) -> Result<(u8, u8, u8)> {
+    let prefix = format!("# {}-", name);
+    let bad = || format!("{}.txt: couldn't find the version in its first line", name);
+    let first = src.lines().next().unwrap_or_default();
+    let v = first
+        .strip_prefix(&prefix)
+        .and_then(|s| s.strip_suffix(".txt"))
+        .ok_or_else(bad)?;
+    let parts = v
+        .split('.')
+        .map(|p| p.parse::<u8>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| bad())?;
+    match parts[..] {
+        [a, b, c] => Ok((a, b, c)),
+        _ => Err(bad().into()),
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Eaw {
+    Neutral,
+    Narrow,
+    Halfwidth,
+    Wide,
+    Fullwidth,
+    Ambiguous,
+}
+
+pub struct Ucd {
+    version: (u8, u8, u8),
+    /// Two letter general category, indexed by codepoint.
+    category: Vec<[u8; 2]>,
+    eaw: Vec<Eaw>,
+    /// Emoji that were narrow before Unicode 9 made them wide.
+    widened: Vec<bool>,
+    /// Emoji added in Unicode 9 or later, which have always been wide.
+    wide_emoji: Vec<bool>,
+    /// `(lo, hi, (major, minor))`, the version each range was assigned in.
+    age: Vec<(u32, u32, (u8, u8))>,
+}
+
+impl Ucd {
+    pub fn load(dir: &Path) -> Result<Self> {
+        let eaw_src = read(dir, "EastAsianWidth.txt")?;
+        let gc_src = read(dir, "DerivedGeneralCategory.txt")?;
+        let version = file_version(&eaw_src, "EastAsianWidth")?;
+        let gc_version = file_version(&gc_src, "DerivedGeneralCategory")?;
+        if version != gc_version {
+            return Err(format!(
+                "EastAsianWidth.txt is for unicode {:?}, but DerivedGeneralCategory.txt is for {:?}",
+                version, gc_version,
+            )
+            .into());
+        }
+        let n = MAX_CP as usize + 1;
+        let mut ucd = Self {
+            version,
+            category: vec![*b"Cn"; n],
+            eaw: vec![Eaw::Neutral; n],
+            widened: vec![false; n],
+            wide_emoji: vec![false; n],
+            age: vec![],
+        };
+        ucd.load_unicode_data(&read(dir, "UnicodeData.txt")?)?;
+        ucd.check_unassigned(&gc_src)?;
+        ucd.load_east_asian_width(&eaw_src)?;
+        // The emoji widths depend on the ages.
+        ucd.load_age(&read(dir, "DerivedAge.txt")?)?;
+        ucd.load_emoji_data(&read(dir, "emoji-data.txt")?)?;
+        Ok(ucd)
+    }
+
+    pub fn version_string(&self) -> String {
+        let (a, b, c) = self.version;
+        format!("{}.{}.{}", a, b, c)
+    }
+
+    fn load_unicode_data(&mut self, src: &str) -> Result<()> {
+        // Big blocks like CJK ideographs are written as a pair of lines, with
+        // names like `<CJK Ideograph, First>` and `<CJK Ideograph, Last>`.
+        let mut range_start = None;
+        for line in parse_datafile(src)? {
+            let (name, gc) = match &line.fields[..] {
+                [name, gc, ..] if gc.len() == 2 => (name, gc.as_bytes()),
+                _ => return Err(format!("UnicodeData.txt: bad line for {:04X}", line.lo).into()),
+            };
+            let lo = if name.ends_with(", First>") {
+                range_start = Some(line.lo);
+                continue;
+            } else if name.ends_with(", Last>") {
+                range_start
+                    .take()
+                    .ok_or_else(|| format!("{} without a First", name))?
+            } else {
+                line.lo
+            };
+            for cp in lo..=line.hi {
+                self.category[cp as usize] = [gc[0], gc[1]];
+            }
+        }
+        Ok(())
+    }
+
+    // UnicodeData.txt doesn't list unassigned codepoints at all, so the only
+    // thing DerivedGeneralCategory.txt tells us is whether the two agree.
+    fn check_unassigned(&self, src: &str) -> Result<()> {
+        for line in parse_datafile(src)? {
+            let unassigned = line.fields.first().map(|s| s.as_str()) == Some("Cn");
+            for cp in line.lo..=line.hi {
+                if unassigned != (&self.category[cp as usize] == b"Cn") {
+                    return Err(format!(
+                        "UnicodeData.txt and DerivedGeneralCategory.txt disagree about U+{:04X}, \
+                         are they from the same unicode version?",
+                        cp,
+                    )
+                    .into());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn load_east_asian_width(&mut self, src: &str) -> Result<()> {
+        // Unlisted codepoints get the defaults from the `# @missing:` lines,
+        // which newer files have. Older ones only describe them in the header:
+        // these blocks are W, and everything else unlisted is N.
+        let missing = src
+            .lines()
+            .filter_map(|l| l.strip_prefix("# @missing:"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let defaults = if missing.is_empty() {
+            "3400..4DBF;W\n4E00..9FFF;W\nF900..FAFF;W\n20000..2FFFD;W\n30000..3FFFD;W".to_string()
+        } else {
+            missing
+        };
+        for line in parse_datafile(&defaults)?
+            .into_iter()
+            .chain(parse_datafile(src)?)
+        {
+            let eaw = match line.fields.first().map(|s| s.as_str()) {
+                Some("N") => Eaw::Neutral,
+                Some("Na") => Eaw::Narrow,
+                Some("H") => Eaw::Halfwidth,
+                Some("W") => Eaw::Wide,
+                Some("F") => Eaw::Fullwidth,
+                Some("A") => Eaw::Ambiguous,
+                other => {
+                    return Err(format!("EastAsianWidth.txt: unknown width {:?}", other).into())
+                }
+            };
+            for cp in line.lo..=line.hi {
+                self.eaw[cp as usize] = eaw;
+            }
+        }
+        Ok(())
+    }
+
+    /// The same rule as widecharwidth's `generate.py`: emoji from U+1F000 up
+    /// are wide, unless East Asian Width says they're narrow, and the ones
+    /// from before Unicode 9 were widened by it.
+    fn load_emoji_data(&mut self, src: &str) -> Result<()> {
+        for line in parse_datafile(src)? {
+            // Below U+1F000 things can only become emoji by way of a variation
+            // selector, which wcwidth can't do anything sensible with.
+            for cp in line.lo.max(0x1F000)..=line.hi {
+                // `generate.py` reads the version out of the comment, which
+                // used to be the unicode version the character was added in,
+                // but is the emoji version in newer files (so 1F000 MAHJONG
+                // TILE EAST WIND went from `5.1` to `E0.0`). The age is what
+                // it meant, so use that.
+                let age = match self.age_of(cp) {
+                    Some(age) => age,
+                    // Reserved for future emoji.
+                    None => continue,
+                };
+                if matches!(self.eaw[cp as usize], Eaw::Narrow | Eaw::Halfwidth) {
+                    continue;
+                }
+                if age < (9, 0) {
+                    self.widened[cp as usize] = true;
+                } else {
+                    self.wide_emoji[cp as usize] = true;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn age_of(&self, cp: u32) -> Option<(u8, u8)> {
+        let i = self.age.partition_point(|r| r.1 < cp);
+        self.age.get(i).filter(|r| r.0 <= cp).map(|r| r.2)
+    }
+
+    fn load_age(&mut self, src: &str) -> Result<()> {
+        for line in parse_datafile(src)? {
+            let v = line.fields.first().map(|s| s.as_str()).unwrap_or_default();
+            let bad = || format!("DerivedAge.txt: bad version {:?}", v);
+            let (major, minor) = v.split_once('.').ok_or_else(bad)?;
+            let v = (
+                major.parse().map_err(|_| bad())?,
+                minor.parse().map_err(|_| bad())?,
+            );
+            if v > (self.version.0, self.version.1) {
+                return Err(format!(
+                    "DerivedAge.txt mentions unicode {:?}, newer than the rest",
+                    v
+                )
+                .into());
+            }
+            self.age.push((line.lo, line.hi, v));
+        }
+        self.age.sort_unstable();
+        self.age.dedup_by(|next, prev| {
+            let merge = prev.1 + 1 == next.0 && prev.2 == next.2;
+            if merge {
+                prev.1 = next.1;
+            }
+            merge
+        });
+        Ok(())
+    }
+
+    pub fn tables(&self) -> Tables {
+        let ranges = |pred: &dyn Fn(usize) -> bool| {
+            let mut out: Vec<(u32, u32)> = vec![];
+            for cp in (0..=MAX_CP).filter(|&cp| pred(cp as usize)) {
+                match out.last_mut() {
+                    Some(last) if last.1 + 1 == cp => last.1 = cp,
+                    _ => out.push((cp, cp)),
+                }
+            }
+            out
+        };
+        let gc_in = |cp: usize, cats: &[&[u8; 2]]| cats.contains(&&self.category[cp]);
+        Tables {
+            version: self.version,
+            private: ranges(&|cp| gc_in(cp, &[b"Co"])),
+            nonprint: ranges(&|cp| gc_in(cp, &[b"Cc", b"Cf", b"Zl", b"Zp", b"Cs"])),
+            combining: ranges(&|cp| gc_in(cp, &[b"Mn", b"Mc", b"Me"])),
+            doublewide: ranges(&|cp| {
+                self.wide_emoji[cp]
+                    || matches!(self.eaw[cp], Eaw::Wide | Eaw::Fullwidth) && !self.widened[cp]
+            }),
+            ambiguous: ranges(&|cp| {
+                self.eaw[cp] == Eaw::Ambiguous && !self.widened[cp] && !self.wide_emoji[cp]
+            }),
+            unassigned: ranges(&|cp| gc_in(cp, &[b"Cn"])),
+            widened: ranges(&|cp| self.widened[cp]),
+            age: self.age.clone(),
+        }
+    }
+}
+
+pub struct Tables {
+    pub version: (u8, u8, u8),
+    pub private: Vec<(u32, u32)>,
+    pub nonprint: Vec<(u32, u32)>,
+    pub combining: Vec<(u32, u32)>,
+    pub doublewide: Vec<(u32, u32)>,
+    pub ambiguous: Vec<(u32, u32)>,
+    pub unassigned: Vec<(u32, u32)>,
+    pub widened: Vec<(u32, u32)>,
+    pub age: Vec<(u32, u32, (u8, u8))>,
+}
+
+fn render_table(out: &mut String, doc: &str, name: &str, ranges: &[(u32, u32)]) {
+    let _ = writeln!(out, "\n/// {}", doc);
+    out.push_str("#[rustfmt::skip]\n");
+    let _ = writeln!(
+        out,
+        "pub const {}: [(u32, u32); {}] = [",
+        name,
+        ranges.len()
+    );
+    for row in ranges.chunks(3) {
+        let row = row
+            .iter()
+            .map(|(lo, hi)| format!("(0x{:05X}, 0x{:05X}),", lo, hi))
+            .collect::<Vec<_>>();
+        let _ = writeln!(out, "    {}", row.join(" "));
+    }
+    out.push_str("];\n");
+}
+
+pub fn render(t: &Tables) -> String {
+    let (a, b, c) = t.version;
+    let mut out = format!(
+        "//! Width tables for `widecharwidth`, generated by `cargo xtask gen-tables`\n\
+         //! from the Unicode {}.{}.{} data files. Don't edit by hand.\n\
+         \n\
+         /// Unicode version these tables were generated from.\n\
+         pub const UNICODE_VERSION: (u8, u8, u8) = ({}, {}, {});\n",
+        a, b, c, a, b, c,
+    );
+    render_table(
+        &mut out,
+        "Simple ASCII characters - used a lot, so we check them first.",
+        "WIDECHAR_ASCII_TABLE",
+        &[(0x20, 0x7E)],
+    );
+    for (doc, name, ranges) in &[
+        ("Private usage range.", "WIDECHAR_PRIVATE_TABLE", &t.private),
+        (
+            "Nonprinting characters.",
+            "WIDECHAR_NONPRINT_TABLE",
+            &t.nonprint,
+        ),
+        (
+            "Width 0 combining marks.",
+            "WIDECHAR_COMBINING_TABLE",
+            &t.combining,
+        ),
+        (
+            "Width 2 characters.",
+            "WIDECHAR_DOUBLEWIDE_TABLE",
+            &t.doublewide,
+        ),
+        (
+            "Ambiguous-width characters.",
+            "WIDECHAR_AMBIGUOUS_TABLE",
+            &t.ambiguous,
+        ),
+        (
+            "Unassigned characters.",
+            "WIDECHAR_UNASSIGNED_TABLE",
+            &t.unassigned,
+        ),
+        (
+            "Characters that were widened from with 1 to 2 in Unicode 9.",
+            "WIDECHAR_WIDENED_TABLE",
+            &t.widened,
+        ),
+    ] {
+        render_table(&mut out, doc, name, ranges);
+    }
+    out.push_str("\n/// The Unicode version each assigned character first appeared in.\n");
+    out.push_str("#[rustfmt::skip]\n");
+    let _ = writeln!(
+        out,
+        "pub static WIDECHAR_AGE_TABLE: [(u32, u32, u8, u8); {}] = [",
+        t.age.len()
+    );
+    for row in t.age.chunks(3) {
+        let row = row
+            .iter()
+            .map(|(lo, hi, (major, minor))| {
+                format!("(0x{:05X}, 0x{:05X}, {}, {}),", lo, hi, major, minor)
+            })
+            .collect::<Vec<_>>();
+        let _ = writeln!(out, "    {}", row.join(" "));
+    }
+    out.push_str("];\n");
+    out
+}